@@ -0,0 +1,48 @@
+;; Sample WASM processor: drops events containing "DEBUG" and keeps every other event unchanged.
+;; Build with `wat2wasm plugins/drop_debug.wat -o drop_debug.wasm`.
+(module
+  (memory (export "memory") 1)
+  (global $heap (mut i32) (i32.const 1024))
+
+  ;; returned for dropped events: pointer 0, length 4
+  (data (i32.const 0) "null")
+
+  ;; bump allocator, every call runs in a fresh instance
+  (func (export "alloc") (param $len i32) (result i32)
+    (local $ptr i32)
+    (local $needed i32)
+    (local.set $ptr (global.get $heap))
+    (global.set $heap (i32.add (global.get $heap) (local.get $len)))
+    (local.set $needed
+      (i32.sub (global.get $heap) (i32.mul (memory.size) (i32.const 65536))))
+    (if (i32.gt_s (local.get $needed) (i32.const 0))
+      (then
+        (if (i32.eq
+              (memory.grow (i32.add (i32.div_u (local.get $needed) (i32.const 65536)) (i32.const 1)))
+              (i32.const -1))
+          (then unreachable))))
+    (local.get $ptr))
+
+  (func (export "process") (param $ptr i32) (param $len i32) (result i64)
+    (local $i i32)
+    (local $end i32)
+    (local.set $i (local.get $ptr))
+    (local.set $end (i32.sub (i32.add (local.get $ptr) (local.get $len)) (i32.const 5)))
+    (block $done
+      (loop $scan
+        (br_if $done (i32.gt_s (local.get $i) (local.get $end)))
+        (if (i32.and
+              (i32.and
+                (i32.eq (i32.load8_u (local.get $i)) (i32.const 68))
+                (i32.eq (i32.load8_u offset=1 (local.get $i)) (i32.const 69)))
+              (i32.and
+                (i32.and
+                  (i32.eq (i32.load8_u offset=2 (local.get $i)) (i32.const 66))
+                  (i32.eq (i32.load8_u offset=3 (local.get $i)) (i32.const 85)))
+                (i32.eq (i32.load8_u offset=4 (local.get $i)) (i32.const 71))))
+          (then (return (i64.const 4))))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $scan)))
+    ;; 0 keeps the event unchanged
+    (i64.const 0))
+)
@@ -0,0 +1,305 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::{sync::mpsc, task};
+use tracing::{error, info};
+
+use crate::{
+    checkpoint::{Checkpoint, CheckpointStore},
+    cli::BackfillArgs,
+    config::{global_config, OutputConfig, SourceConfig},
+    dedup::Deduplicator,
+    detector::inode,
+    event_bucket,
+    log_event::{LogEvent, SourceMeta},
+    processor::{Pipeline, SourceStage},
+    sender::{
+        self,
+        delivery::{DeliveryOutcome, DeliveryReport, OffsetCommitter},
+        payload::Payload,
+    },
+};
+
+pub mod error;
+pub use error::BackfillError;
+
+mod reader;
+use self::reader::ArchiveReader;
+
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(10);
+const STATE_SAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+struct BackfillFile {
+    path: PathBuf,
+    // canonical path, the key of the file in the state
+    key: String,
+    inode: u64,
+}
+
+#[derive(Default)]
+struct Totals {
+    files: usize,
+    lines: u64,
+    bytes: u64,
+}
+
+// sends existing files once through the pipeline of one source, delivered offsets are
+// saved per file so an interrupted backfill continues where it stopped
+pub async fn run(args: BackfillArgs, sources: Vec<SourceConfig>, outputs: Vec<OutputConfig>) -> Result<(), BackfillError> {
+    let source = sources
+        .into_iter()
+        .find(|source| source.name == args.source)
+        .ok_or_else(|| BackfillError::UnknownSource(args.source.clone()))?;
+    let files = expand(&args.patterns)?;
+    let state = Arc::new(CheckpointStore::load(&args.state_path)?);
+
+    let channel_bound = global_config().channel_bound;
+    let (event_sender, event_receiver) = mpsc::channel::<LogEvent>(channel_bound);
+    let (payload_sender, payload_receiver) = mpsc::channel::<Payload>(channel_bound);
+    let (report_sender, report_receiver) = mpsc::unbounded_channel::<DeliveryReport>();
+
+    let stage = SourceStage::new(Pipeline::build(&source)?, event_sender);
+    let deduplicator = Deduplicator::build(std::slice::from_ref(&source));
+
+    let aggregator_handle = event_bucket::spawn_event_aggregator(event_receiver, payload_sender, deduplicator);
+    let sender_handle = sender::spawn_sender(payload_receiver, outputs, report_sender)?;
+
+    let keys = files.iter().map(|file| (file.inode, file.key.clone())).collect();
+    let recorder_handle = spawn_state_recorder(report_receiver, state.clone(), keys);
+
+    let reader_state = state.clone();
+    let totals = task::spawn_blocking(move || read_files(&source, &files, &reader_state, stage))
+        .await
+        .map_err(|_| BackfillError::ChannelClosed)??;
+
+    // the stages finish once everything read was sent
+    let _ = aggregator_handle.await;
+    let _ = sender_handle.await;
+    let failed = recorder_handle.await.unwrap_or_default();
+
+    state.save()?;
+    info!("backfill finished: {} lines ({} bytes) from {} files", totals.lines, totals.bytes, totals.files);
+    if failed > 0 {
+        return Err(BackfillError::PayloadsFailed(failed));
+    }
+
+    Ok(())
+}
+
+// files of every pattern, a pattern's matches oldest first so rotated archives are sent in the order they were written
+fn expand(patterns: &[String]) -> Result<Vec<BackfillFile>, BackfillError> {
+    let mut files = Vec::new();
+    let mut seen = HashSet::new();
+
+    for pattern in patterns {
+        let mut matched = Vec::new();
+        for path in glob::glob(pattern)? {
+            let path = path?;
+            let meta = fs::metadata(&path)?;
+            if meta.is_file() {
+                matched.push((meta.modified()?, path, meta));
+            }
+        }
+
+        if matched.is_empty() {
+            return Err(BackfillError::NoMatch(pattern.clone()));
+        }
+        matched.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.cmp(&b.1)));
+
+        for (_, path, meta) in matched {
+            let key = fs::canonicalize(&path)?.to_string_lossy().into_owned();
+            if seen.insert(key.clone()) {
+                files.push(BackfillFile { path, key, inode: inode(&meta) });
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+fn read_files(
+    source: &SourceConfig,
+    files: &[BackfillFile],
+    state: &CheckpointStore,
+    stage: SourceStage,
+) -> Result<Totals, BackfillError> {
+    let mut totals = Totals::default();
+
+    for (i, file) in files.iter().enumerate() {
+        let mut reader = ArchiveReader::open(&file.path)?;
+        let meta = Arc::new(SourceMeta {
+            path: file.key.clone(),
+            inode: file.inode,
+            labels: source.labels.clone(),
+        });
+
+        if let Some(checkpoint) = state.get(&file.key).filter(|checkpoint| checkpoint.inode == file.inode) {
+            info!("[{}] resuming {} at offset {}", source.name, file.key, checkpoint.offset);
+            reader.skip_to(checkpoint.offset)?;
+        }
+
+        info!("[{}] backfilling {} ({}/{})", source.name, file.key, i + 1, files.len());
+        let start_position = reader.position();
+        let mut lines = 0;
+        let mut last_progress = Instant::now();
+
+        while let Some(line) = reader.next_line()? {
+            lines += 1;
+            if last_progress.elapsed() >= PROGRESS_INTERVAL {
+                info!("[{}] {}: {lines} lines, {} bytes read", source.name, file.key, reader.position() - start_position);
+                last_progress = Instant::now();
+            }
+
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut event = LogEvent::new(source.name.clone(), line, reader.position());
+            event.meta = Some(meta.clone());
+            event.line_number = reader.line_number();
+
+            stage.send(event).map_err(|_| BackfillError::ChannelClosed)?;
+        }
+
+        let bytes = reader.position() - start_position;
+        info!("[{}] finished {}: {lines} lines, {bytes} bytes", source.name, file.key);
+
+        totals.files += 1;
+        totals.lines += lines;
+        totals.bytes += bytes;
+    }
+
+    stage.finish().map_err(|_| BackfillError::ChannelClosed)?;
+    Ok(totals)
+}
+
+// records delivered offsets per file, returns the number of payloads that failed permanently.
+// a failed payload stops its source from moving forward so a rerun sends its lines again
+fn spawn_state_recorder(
+    mut report_receiver: mpsc::UnboundedReceiver<DeliveryReport>,
+    state: Arc<CheckpointStore>,
+    keys: HashMap<u64, String>,
+) -> task::JoinHandle<u64> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(STATE_SAVE_INTERVAL);
+        let mut committer = OffsetCommitter::new(false);
+        let mut failed = 0;
+
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    if let Err(e) = state.save() {
+                        error!("failed to save backfill state: {e}");
+                    }
+                }
+                report = report_receiver.recv() => {
+                    let Some(report) = report else { break };
+
+                    if report.outcome == DeliveryOutcome::FailedPermanently {
+                        failed += 1;
+                    }
+
+                    for offset in committer.resolve(&report) {
+                        let Some(inode) = offset.inode else { continue };
+                        if let Some(key) = keys.get(&inode) {
+                            state.record(key, Checkpoint { inode, offset: offset.offset, cursor: None });
+                        }
+                    }
+                }
+            }
+        }
+
+        failed
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempPath;
+
+    #[test]
+    fn expands_globs_oldest_first() {
+        let dir = TempPath::new("backfill");
+        fs::create_dir_all(&dir).unwrap();
+
+        let older = dir.join("app.log.2");
+        let newer = dir.join("app.log.1");
+        fs::write(&older, "old\n").unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&older)
+            .unwrap()
+            .set_modified(std::time::SystemTime::now() - Duration::from_secs(60))
+            .unwrap();
+        fs::write(&newer, "new\n").unwrap();
+
+        let patterns = [format!("{}/app.log.*", dir.display()), newer.display().to_string()];
+        let files = expand(&patterns).unwrap();
+        let names: Vec<_> = files.iter().map(|file| file.path.file_name().unwrap().to_str().unwrap()).collect();
+        assert_eq!(names, ["app.log.2", "app.log.1"]);
+
+        let missing = [format!("{}/other.log*", dir.display())];
+        assert!(matches!(expand(&missing), Err(BackfillError::NoMatch(_))));
+    }
+
+    #[test]
+    fn resumes_from_state() {
+        let dir = TempPath::new("backfill-resume");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("app.log.1");
+        fs::write(&path, "one\ntwo\nthree\n").unwrap();
+
+        let source: SourceConfig = toml::from_str(
+            r#"
+                name = "app1"
+                log_path = "app1.log"
+            "#,
+        )
+        .unwrap();
+        let files = expand(&[path.display().to_string()]).unwrap();
+        let state = CheckpointStore::load(dir.join("state.json")).unwrap();
+        state.record(&files[0].key, Checkpoint { inode: files[0].inode, offset: 4, cursor: None });
+
+        let (event_sender, mut event_receiver) = mpsc::channel(8);
+        let stage = SourceStage::new(Pipeline::build(&source).unwrap(), event_sender);
+        let totals = read_files(&source, &files, &state, stage).unwrap();
+
+        assert_eq!(totals.lines, 2);
+        let event = event_receiver.try_recv().unwrap();
+        assert_eq!((event.log.as_str(), event.line_number, event.offset), ("two", 2, 8));
+        assert_eq!(event.meta.unwrap().path, files[0].key);
+        assert_eq!(event_receiver.try_recv().unwrap().log, "three");
+    }
+
+    #[tokio::test]
+    async fn failed_payload_is_not_skipped_in_state() {
+        let path = TempPath::new("backfill-state.json");
+        let state = Arc::new(CheckpointStore::load(&*path).unwrap());
+        let (report_sender, report_receiver) = mpsc::unbounded_channel();
+        let recorder = spawn_state_recorder(report_receiver, state.clone(), HashMap::from([(7, "app.log.1".to_string())]));
+
+        let report = |seq: u64, offset: u64, outcome| DeliveryReport {
+            payload_id: seq,
+            offsets: vec![sender::payload::SourceOffset {
+                source_name: "app1".to_string(),
+                inode: Some(7),
+                offset,
+                cursor: None,
+                seq,
+            }],
+            outcome,
+        };
+        report_sender.send(report(0, 10, DeliveryOutcome::Delivered)).unwrap();
+        report_sender.send(report(2, 30, DeliveryOutcome::Delivered)).unwrap();
+        report_sender.send(report(1, 20, DeliveryOutcome::FailedPermanently)).unwrap();
+        drop(report_sender);
+
+        assert_eq!(recorder.await.unwrap(), 1);
+        assert_eq!(state.get("app.log.1").map(|checkpoint| checkpoint.offset), Some(10));
+    }
+}
@@ -0,0 +1,71 @@
+use std::fmt::{Display, Formatter};
+use std::io;
+
+use crate::{checkpoint::CheckpointError, processor::ProcessorError, sender::SenderError};
+
+#[derive(Debug)]
+pub enum BackfillError {
+    UnknownSource(String),
+    InvalidPattern(glob::PatternError),
+    NoMatch(String),
+    Io(io::Error),
+    State(CheckpointError),
+    Processor(ProcessorError),
+    Sender(SenderError),
+    ChannelClosed,
+    PayloadsFailed(u64),
+}
+
+impl From<glob::PatternError> for BackfillError {
+    fn from(value: glob::PatternError) -> Self {
+        BackfillError::InvalidPattern(value)
+    }
+}
+
+impl From<glob::GlobError> for BackfillError {
+    fn from(value: glob::GlobError) -> Self {
+        BackfillError::Io(value.into())
+    }
+}
+
+impl From<io::Error> for BackfillError {
+    fn from(value: io::Error) -> Self {
+        BackfillError::Io(value)
+    }
+}
+
+impl From<CheckpointError> for BackfillError {
+    fn from(value: CheckpointError) -> Self {
+        BackfillError::State(value)
+    }
+}
+
+impl From<ProcessorError> for BackfillError {
+    fn from(value: ProcessorError) -> Self {
+        BackfillError::Processor(value)
+    }
+}
+
+impl From<SenderError> for BackfillError {
+    fn from(value: SenderError) -> Self {
+        BackfillError::Sender(value)
+    }
+}
+
+impl Display for BackfillError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BackfillError::UnknownSource(name) => write!(f, "Unknown source '{name}'"),
+            BackfillError::InvalidPattern(e) => write!(f, "Invalid file pattern: {e}"),
+            BackfillError::NoMatch(pattern) => write!(f, "No file matches '{pattern}'"),
+            BackfillError::Io(e) => write!(f, "Backfill read error: {e}"),
+            BackfillError::State(e) => write!(f, "Backfill state error: {e}"),
+            BackfillError::Processor(e) => write!(f, "{e}"),
+            BackfillError::Sender(e) => write!(f, "{e}"),
+            BackfillError::ChannelClosed => write!(f, "Event channel closed before the backfill finished"),
+            BackfillError::PayloadsFailed(count) => {
+                write!(f, "{count} payloads failed permanently, run the backfill again to resend them")
+            }
+        }
+    }
+}
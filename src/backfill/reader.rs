@@ -0,0 +1,128 @@
+use flate2::read::MultiGzDecoder;
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::Path,
+};
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+// reads lines of a plain, gzip or zstd file, detected by the magic bytes rather than the extension
+pub struct ArchiveReader {
+    reader: Box<dyn BufRead + Send>,
+    // decompressed bytes read so far
+    position: u64,
+    line_number: u64,
+    buf: Vec<u8>,
+}
+
+impl ArchiveReader {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mut file = BufReader::with_capacity(64 * 1024, File::open(path)?);
+        let magic = file.fill_buf()?;
+
+        let reader: Box<dyn BufRead + Send> = if magic.starts_with(&GZIP_MAGIC) {
+            Box::new(BufReader::with_capacity(64 * 1024, MultiGzDecoder::new(file)))
+        } else if magic.starts_with(&ZSTD_MAGIC) {
+            Box::new(BufReader::with_capacity(64 * 1024, zstd::Decoder::with_buffer(file)?))
+        } else {
+            Box::new(file)
+        };
+
+        Ok(Self {
+            reader,
+            position: 0,
+            line_number: 0,
+            buf: Vec::with_capacity(1024),
+        })
+    }
+
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    pub fn line_number(&self) -> u64 {
+        self.line_number
+    }
+
+    // invalid UTF-8 is replaced instead of failing the whole archive
+    pub fn next_line(&mut self) -> io::Result<Option<String>> {
+        self.buf.clear();
+        let bytes = self.reader.read_until(b'\n', &mut self.buf)?;
+        if bytes == 0 {
+            return Ok(None);
+        }
+
+        self.position += bytes as u64;
+        self.line_number += 1;
+
+        while matches!(self.buf.last(), Some(b'\n' | b'\r')) {
+            self.buf.pop();
+        }
+
+        Ok(Some(String::from_utf8_lossy(&self.buf).into_owned()))
+    }
+
+    // skips whole lines until `offset`, compressed files have to be decompressed up to it
+    pub fn skip_to(&mut self, offset: u64) -> io::Result<()> {
+        while self.position < offset {
+            if self.next_line()?.is_none() {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::GzEncoder, Compression};
+    use crate::test_util::TempPath;
+    use std::{fs, io::Write};
+
+    const CONTENT: &[u8] = b"first\nsecond\r\nthird";
+
+    fn write(name: &str, bytes: &[u8]) -> TempPath {
+        let path = TempPath::new(name);
+        fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    fn lines(path: &Path) -> Vec<String> {
+        let mut reader = ArchiveReader::open(path).unwrap();
+        let mut lines = Vec::new();
+        while let Some(line) = reader.next_line().unwrap() {
+            lines.push(line);
+        }
+        lines
+    }
+
+    #[test]
+    fn reads_plain_gzip_and_zstd() {
+        let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+        gzip.write_all(CONTENT).unwrap();
+
+        let paths = [
+            write("plain.log", CONTENT),
+            write("app.log.2.gz", &gzip.finish().unwrap()),
+            write("app.log.3", &zstd::encode_all(CONTENT, 0).unwrap()),
+        ];
+
+        for path in &paths {
+            assert_eq!(lines(path), ["first", "second", "third"], "{}", path.display());
+        }
+    }
+
+    #[test]
+    fn skips_to_offset() {
+        let path = write("skip.log", CONTENT);
+        let mut reader = ArchiveReader::open(&path).unwrap();
+
+        reader.skip_to(6).unwrap();
+        assert_eq!(reader.next_line().unwrap().as_deref(), Some("second"));
+        assert_eq!((reader.position(), reader.line_number()), (14, 2));
+    }
+}
@@ -0,0 +1,133 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+use tracing::debug;
+
+pub mod error;
+pub use error::CheckpointError;
+
+// end of the last delivered line of a file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub inode: u64,
+    pub offset: u64,
+    // journal cursor of the last delivered entry, journald sources only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+}
+
+// delivered offsets per source, saved as JSON
+pub struct CheckpointStore {
+    path: PathBuf,
+    checkpoints: Mutex<BTreeMap<String, Checkpoint>>,
+    dirty: AtomicBool,
+}
+
+impl CheckpointStore {
+    // a missing file is an empty store, it is created on the first save
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self, CheckpointError> {
+        let path = path.into();
+        let checkpoints = match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(Self {
+            path,
+            checkpoints: Mutex::new(checkpoints),
+            dirty: AtomicBool::new(false),
+        })
+    }
+
+    pub fn get(&self, source_name: &str) -> Option<Checkpoint> {
+        self.lock().get(source_name).cloned()
+    }
+
+    // offsets only move forward within a file, another inode means the file was rotated
+    pub fn record(&self, source_name: &str, checkpoint: Checkpoint) {
+        let mut checkpoints = self.lock();
+
+        match checkpoints.get_mut(source_name) {
+            Some(current) if current.inode == checkpoint.inode && current.offset >= checkpoint.offset => return,
+            Some(current) => *current = checkpoint,
+            None => {
+                checkpoints.insert(source_name.to_string(), checkpoint);
+            }
+        }
+
+        self.dirty.store(true, Ordering::Relaxed);
+    }
+
+    // written to a temporary file first so a crash never leaves a torn checkpoint file
+    pub fn save(&self) -> Result<(), CheckpointError> {
+        if !self.dirty.swap(false, Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        let json = serde_json::to_vec_pretty(&*self.lock())?;
+        let tmp_path = self.path.with_extension("tmp");
+
+        let result = fs::write(&tmp_path, json).and_then(|()| fs::rename(&tmp_path, &self.path));
+        if result.is_err() {
+            self.dirty.store(true, Ordering::Relaxed);
+        }
+        result?;
+
+        debug!("saved checkpoints to {}", self.path.display());
+        Ok(())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, Checkpoint>> {
+        self.checkpoints.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempPath;
+
+    #[test]
+    fn offsets_only_move_forward_within_a_file() {
+        let path = TempPath::new("checkpoint-forward.json");
+        let store = CheckpointStore::load(&*path).unwrap();
+
+        store.record("app1", Checkpoint { inode: 1, offset: 100, cursor: None });
+        store.record("app1", Checkpoint { inode: 1, offset: 50, cursor: None });
+        assert_eq!(store.get("app1"), Some(Checkpoint { inode: 1, offset: 100, cursor: None }));
+
+        store.record("app1", Checkpoint { inode: 2, offset: 10, cursor: None });
+        assert_eq!(store.get("app1"), Some(Checkpoint { inode: 2, offset: 10, cursor: None }));
+    }
+
+    #[test]
+    fn saved_checkpoints_are_loaded() {
+        let path = TempPath::new("checkpoint-save.json");
+
+        let store = CheckpointStore::load(&*path).unwrap();
+        store.record("app1", Checkpoint { inode: 7, offset: 42, cursor: None });
+        store.record("journal", Checkpoint { inode: 0, offset: 1700000000000000, cursor: Some("s=abc;i=1".to_string()) });
+        store.save().unwrap();
+
+        let loaded = CheckpointStore::load(&*path).unwrap();
+        assert_eq!(loaded.get("app1"), Some(Checkpoint { inode: 7, offset: 42, cursor: None }));
+        assert_eq!(loaded.get("journal").and_then(|checkpoint| checkpoint.cursor).as_deref(), Some("s=abc;i=1"));
+        assert_eq!(loaded.get("app2"), None);
+    }
+
+    #[test]
+    fn invalid_file_is_error() {
+        let path = TempPath::new("checkpoint-invalid.json");
+        fs::write(&path, "not json").unwrap();
+
+        assert!(matches!(CheckpointStore::load(&*path), Err(CheckpointError::InvalidFile(_))));
+    }
+}
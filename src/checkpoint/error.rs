@@ -0,0 +1,29 @@
+use std::fmt::{Display, Formatter};
+use std::io;
+
+#[derive(Debug)]
+pub enum CheckpointError {
+    Io(io::Error),
+    InvalidFile(serde_json::Error),
+}
+
+impl From<io::Error> for CheckpointError {
+    fn from(value: io::Error) -> Self {
+        CheckpointError::Io(value)
+    }
+}
+
+impl From<serde_json::Error> for CheckpointError {
+    fn from(value: serde_json::Error) -> Self {
+        CheckpointError::InvalidFile(value)
+    }
+}
+
+impl Display for CheckpointError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CheckpointError::Io(e) => write!(f, "Checkpoint file error: {e}"),
+            CheckpointError::InvalidFile(e) => write!(f, "Invalid checkpoint file: {e}"),
+        }
+    }
+}
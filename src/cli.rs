@@ -0,0 +1,116 @@
+use std::fmt::{Display, Formatter};
+
+pub const USAGE: &str = "\
+usage:
+  log-agent                                                  tail the configured sources
+  log-agent backfill --source <name> [--state <file>] <file or glob>...
+                                                             send existing files once";
+
+const DEFAULT_STATE_PATH: &str = "log-agent.backfill.json";
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Run,
+    Backfill(BackfillArgs),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct BackfillArgs {
+    // lines are processed and sent as this configured source
+    pub source: String,
+    // delivered offsets per file, a rerun resumes from them
+    pub state_path: String,
+    pub patterns: Vec<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CliError {
+    UnknownCommand(String),
+    UnknownOption(String),
+    MissingValue(&'static str),
+    MissingSource,
+    MissingFiles,
+}
+
+impl Display for CliError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CliError::UnknownCommand(command) => write!(f, "Unknown command '{command}'"),
+            CliError::UnknownOption(option) => write!(f, "Unknown option '{option}'"),
+            CliError::MissingValue(option) => write!(f, "{option} needs a value"),
+            CliError::MissingSource => write!(f, "backfill needs --source"),
+            CliError::MissingFiles => write!(f, "backfill needs at least one file or glob"),
+        }
+    }
+}
+
+// arguments without the program name
+pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Command, CliError> {
+    match args.next().as_deref() {
+        None => Ok(Command::Run),
+        Some("backfill") => parse_backfill(args).map(Command::Backfill),
+        Some(command) => Err(CliError::UnknownCommand(command.to_string())),
+    }
+}
+
+fn parse_backfill(mut args: impl Iterator<Item = String>) -> Result<BackfillArgs, CliError> {
+    let mut source = None;
+    let mut state_path = None;
+    let mut patterns = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--source" => source = Some(args.next().ok_or(CliError::MissingValue("--source"))?),
+            "--state" => state_path = Some(args.next().ok_or(CliError::MissingValue("--state"))?),
+            option if option.starts_with("--") => return Err(CliError::UnknownOption(arg)),
+            _ => patterns.push(arg),
+        }
+    }
+
+    if patterns.is_empty() {
+        return Err(CliError::MissingFiles);
+    }
+
+    Ok(BackfillArgs {
+        source: source.ok_or(CliError::MissingSource)?,
+        state_path: state_path.unwrap_or_else(|| DEFAULT_STATE_PATH.to_string()),
+        patterns,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_args(args: &[&str]) -> Result<Command, CliError> {
+        parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn no_arguments_runs_the_agent() {
+        assert_eq!(parse_args(&[]), Ok(Command::Run));
+    }
+
+    #[test]
+    fn backfill_arguments() {
+        let command = parse_args(&["backfill", "--source", "app1", "/var/log/app.log.1", "/var/log/app.log.*.gz"]).unwrap();
+
+        assert_eq!(
+            command,
+            Command::Backfill(BackfillArgs {
+                source: "app1".to_string(),
+                state_path: DEFAULT_STATE_PATH.to_string(),
+                patterns: vec!["/var/log/app.log.1".to_string(), "/var/log/app.log.*.gz".to_string()],
+            })
+        );
+    }
+
+    #[test]
+    fn backfill_needs_source_and_files() {
+        assert_eq!(parse_args(&["backfill", "app.log"]), Err(CliError::MissingSource));
+        assert_eq!(parse_args(&["backfill", "--source", "app1"]), Err(CliError::MissingFiles));
+        assert_eq!(parse_args(&["backfill", "--source"]), Err(CliError::MissingValue("--source")));
+        assert_eq!(parse_args(&["backfill", "--force", "app.log"]), Err(CliError::UnknownOption("--force".to_string())));
+        assert_eq!(parse_args(&["tail"]), Err(CliError::UnknownCommand("tail".to_string())));
+    }
+}
@@ -1,16 +1,27 @@
+use crate::{detector::journal_priority, filter::LineFilter, parser::LineParser, processor::Pipeline, redact::Redactor};
 use reqwest::Url;
 use serde::Deserialize;
 use std::{collections::HashSet, fs, sync::OnceLock};
 use tracing::{info, warn};
 
+pub mod circuit_breaker_config;
 pub mod config_error;
 pub mod global_config;
+pub mod output_config;
 pub mod source_config;
 
+pub use circuit_breaker_config::CircuitBreakerConfig;
 pub use config_error::ConfigError;
 pub use global_config::GlobalConfig;
+pub use global_config::Labels;
 pub use global_config::SendType;
+pub use output_config::BalanceMode;
+pub use output_config::OutputConfig;
+pub use output_config::OverflowPolicy;
+pub use source_config::LogFormat;
 pub use source_config::SourceConfig;
+pub use source_config::SourceType;
+pub use source_config::StartPosition;
 
 const CONFIG_PATH: &str = "log-agent.config";
 
@@ -20,18 +31,21 @@ static GLOBAL_CONFIG: OnceLock<GlobalConfig> = OnceLock::new();
 pub struct Config {
     pub global: GlobalConfig,
     pub sources: Vec<SourceConfig>,
+
+    #[serde(default)]
+    pub outputs: Vec<OutputConfig>,
 }
 
-pub fn load_config() -> Result<Vec<SourceConfig>, ConfigError> {
+pub fn load_config() -> Result<(Vec<SourceConfig>, Vec<OutputConfig>), ConfigError> {
     let config = parse_config()?;
 
     if GLOBAL_CONFIG.set(config.global).is_err() {
         warn!("GLOBAL_CONFIG is already initialized");
     }
 
-    print_config(&config.sources);
+    print_config(&config.sources, &config.outputs);
 
-    Ok(config.sources)
+    Ok((config.sources, config.outputs))
 }
 
 pub fn global_config() -> &'static GlobalConfig {
@@ -41,7 +55,8 @@ pub fn global_config() -> &'static GlobalConfig {
 }
 
 fn parse_config_from_toml(content: &str) -> Result<Config, ConfigError> {
-    let config: Config = toml::from_str(content)?;
+    let mut config: Config = toml::from_str(content)?;
+    resolve_outputs(&mut config)?;
     valid_config(&config)?;
 
     Ok(config)
@@ -53,17 +68,39 @@ fn parse_config() -> Result<Config, ConfigError> {
     parse_config_from_toml(&content)
 }
 
+// global end_point/send_type is shorthand for a single output receiving every source
+fn resolve_outputs(config: &mut Config) -> Result<(), ConfigError> {
+    let global = &mut config.global;
+    let has_shorthand = global.end_point.is_some() || global.send_type.is_some();
+
+    if !config.outputs.is_empty() {
+        if has_shorthand {
+            return Err(ConfigError::EndPointWithOutputs);
+        }
+        return Ok(());
+    }
+
+    let (Some(end_point), Some(send_type)) = (global.end_point.take(), global.send_type.take()) else {
+        return Err(ConfigError::NoOutput);
+    };
+
+    config.outputs.push(OutputConfig::new(
+        output_config::DEFAULT_OUTPUT_NAME.to_string(),
+        end_point,
+        send_type,
+    ));
+
+    Ok(())
+}
+
 fn valid_config(config: &Config) -> Result<(), ConfigError> {
-    let url = Url::parse(&config.global.end_point)?;
-
-    match config.global.send_type {
-        SendType::HTTP => {
-            if url.scheme() != "http" && url.scheme() != "https" {
-                return Err(ConfigError::InvalidEndPoint(
-                    config.global.end_point.clone(),
-                ));
-            }
+    let mut output_name_set = HashSet::new();
+    for output in &config.outputs {
+        if !output_name_set.insert(&output.name) {
+            return Err(ConfigError::DuplicateOutputName(output.name.to_string()));
         }
+
+        valid_output(output)?;
     }
 
     if config.global.max_send_task < 1 {
@@ -85,22 +122,111 @@ fn valid_config(config: &Config) -> Result<(), ConfigError> {
             return Err(ConfigError::DuplicateSourceName(source.name.to_string()));
         }
 
-        if !path_set.insert(&source.log_path) {
-            return Err(ConfigError::DuplicateLogPath(source.log_path.to_string()));
+        match source.source_type {
+            SourceType::File => {
+                if source.log_path.is_empty() {
+                    return Err(ConfigError::MissingLogPath(source.name.to_string()));
+                }
+
+                if !path_set.insert(&source.log_path) {
+                    return Err(ConfigError::DuplicateLogPath(source.log_path.to_string()));
+                }
+            }
+            SourceType::Journald => valid_journald(source)?,
+        }
+
+        if let Err(e) = LineParser::build(source) {
+            return Err(ConfigError::InvalidParser(source.name.to_string(), e));
+        }
+
+        if let Err(e) = LineFilter::lines(source).and(LineFilter::level(source)) {
+            return Err(ConfigError::InvalidFilter(source.name.to_string(), e));
+        }
+
+        if let Err(e) = Redactor::build(source) {
+            return Err(ConfigError::InvalidRedaction(source.name.to_string(), e));
+        }
+
+        if let Some(rate_limit) = &source.rate_limit {
+            if rate_limit.lines_per_sec == Some(0) || rate_limit.bytes_per_sec == Some(0) {
+                return Err(ConfigError::RateLimitIsUnderOne(source.name.to_string()));
+            }
+        }
+
+        if let Some(sampling) = &source.sampling {
+            if !(sampling.rate > 0.0 && sampling.rate <= 1.0) {
+                return Err(ConfigError::InvalidSampleRate(source.name.to_string()));
+            }
+        }
+
+        if source.dedup.as_ref().is_some_and(|dedup| dedup.window_secs < 1) {
+            return Err(ConfigError::DedupWindowIsUnderOne(source.name.to_string()));
+        }
+
+        if let Err(e) = Pipeline::build(source) {
+            return Err(ConfigError::InvalidPipeline(source.name.to_string(), e));
+        }
+    }
+
+    Ok(())
+}
+
+// a journal has no byte offsets to start from
+fn valid_journald(source: &SourceConfig) -> Result<(), ConfigError> {
+    if let StartPosition::Offset(_) = source.start_position {
+        return Err(ConfigError::InvalidJournalStart(source.name.to_string()));
+    }
+
+    if let Some(priority) = &source.priority {
+        if journal_priority(priority).is_none() {
+            return Err(ConfigError::InvalidPriority(source.name.to_string(), priority.to_string()));
+        }
+    }
+
+    Ok(())
+}
+
+fn valid_output(output: &OutputConfig) -> Result<(), ConfigError> {
+    for end_point in output.end_points() {
+        let url = Url::parse(end_point)?;
+
+        match output.send_type {
+            SendType::HTTP => {
+                if url.scheme() != "http" && url.scheme() != "https" {
+                    return Err(ConfigError::InvalidEndPoint(end_point.clone()));
+                }
+            }
+        }
+    }
+
+    if output.unhealthy_threshold < 1 {
+        return Err(ConfigError::UnhealthyThresholdIsUnderOne);
+    }
+
+    let breaker = &output.circuit_breaker;
+    if breaker.enabled && (breaker.failure_threshold < 1 || breaker.half_open_probes < 1) {
+        return Err(ConfigError::InvalidCircuitBreaker(output.name.clone()));
+    }
+
+    if output.retry_count.is_some_and(|count| count < 1) {
+        return Err(ConfigError::RetryCountIsUnderOne);
+    }
+
+    for pattern in &output.sources {
+        if let Err(e) = glob::Pattern::new(pattern) {
+            return Err(ConfigError::InvalidSourcePattern(pattern.to_string(), e));
         }
     }
 
     Ok(())
 }
 
-fn print_config(sources: &[SourceConfig]) {
+fn print_config(sources: &[SourceConfig], outputs: &[OutputConfig]) {
     let global = global_config();
     info!("Configuration Loaded Successfully");
     info!("----------------------------------");
     info!("Global:");
     info!("\t* Name: {}", global.agent_name);
-    info!("\t* EndPoint: {}", global.end_point);
-    info!("\t* SendType: {:?}", global.send_type);
     info!("\t* Max Send Task: {:?}", global.max_send_task);
     info!("\t* Retry Count: {}", global.retry_count);
     info!("\t* Retry Delay: {}ms", global.retry_delay_ms);
@@ -108,8 +234,60 @@ fn print_config(sources: &[SourceConfig]) {
     info!("Sources ({}):", sources.len());
     sources.iter().enumerate().for_each(|(i, s)| {
         info!("\t{}. {}", i + 1, s.name);
-        info!("\t\t* Path: {}", s.log_path);
+        match s.source_type {
+            SourceType::File => info!("\t\t* Path: {}", s.log_path),
+            SourceType::Journald => {
+                info!("\t\t* Journal: {}", s.journal_dir.as_deref().unwrap_or("system"));
+                if !s.units.is_empty() {
+                    info!("\t\t* Units: {}", s.units.join(", "));
+                }
+                if let Some(priority) = &s.priority {
+                    info!("\t\t* Priority: {priority}");
+                }
+            }
+        }
         info!("\t\t* Delay: {}ms", s.delay_ms);
+        info!("\t\t* Format: {:?}", s.format);
+        if let Some(pattern_name) = &s.pattern_name {
+            info!("\t\t* Pattern: {pattern_name}");
+        }
+        if let Some(timestamp) = &s.timestamp {
+            if let Some(field) = &timestamp.field {
+                info!("\t\t* Timestamp Field: {field}");
+            }
+            if let Some(pattern) = &timestamp.pattern {
+                info!("\t\t* Timestamp Pattern: {pattern}");
+            }
+            info!(
+                "\t\t* Timestamp Format: {} ({})",
+                timestamp.format.as_deref().unwrap_or("auto"),
+                timestamp.timezone.as_deref().unwrap_or("UTC")
+            );
+        }
+    });
+    info!("Outputs ({}):", outputs.len());
+    outputs.iter().enumerate().for_each(|(i, o)| {
+        info!("\t{}. {}", i + 1, o.name);
+        info!("\t\t* EndPoint: {}", o.end_point);
+        if !o.failover_end_points.is_empty() {
+            info!("\t\t* Failover EndPoints: {}", o.failover_end_points.join(", "));
+            info!("\t\t* Balance: {:?}", o.balance);
+            info!("\t\t* Unhealthy After: {} failures ({}s cooldown)", o.unhealthy_threshold, o.unhealthy_cooldown_secs);
+        }
+        info!("\t\t* SendType: {:?}", o.send_type);
+        if o.circuit_breaker.enabled {
+            info!(
+                "\t\t* Circuit Breaker: open after {} failures for {}s, spool {} payloads",
+                o.circuit_breaker.failure_threshold, o.circuit_breaker.open_secs, o.max_spool_size
+            );
+        }
+        if o.sources.is_empty() {
+            info!("\t\t* Sources: *");
+        } else {
+            info!("\t\t* Sources: {}", o.sources.join(", "));
+        }
+        info!("\t\t* Retry Count: {}", o.retry_count.unwrap_or(global.retry_count));
+        info!("\t\t* Retry Delay: {}ms", o.retry_delay_ms.unwrap_or(global.retry_delay_ms));
     });
     info!("----------------------------------");
 }
@@ -137,7 +315,8 @@ mod tests {
 
         let config = parse_config_from_toml(example).expect("parse err");
 
-        assert_eq!(config.global.end_point, "http://localhost:8080/log");
+        assert_eq!(config.outputs.len(), 1);
+        assert_eq!(config.outputs[0].end_point, "http://localhost:8080/log");
         assert_eq!(config.sources.len(), 2);
     }
 
@@ -207,4 +386,307 @@ mod tests {
         let result = parse_config_from_toml(example);
         assert!(matches!(result, Err(ConfigError::DuplicateSourceName(_))));
     }
+
+    #[test]
+    fn journald_source() {
+        let config = |source_lines: &[&str]| {
+            parse_config_from_toml(&format!(
+                r#"
+                    [global]
+                    agent_name = "agent"
+                    end_point = "http://localhost:8080/log"
+                    send_type = "HTTP"
+
+                    [[sources]]
+                    name = "journal"
+                    {}
+                "#,
+                source_lines.join("\n")
+            ))
+        };
+
+        let parsed = config(&[r#"type = "journald""#, r#"units = ["nginx.service"]"#, r#"priority = "warning""#]).expect("parse err");
+        assert_eq!(parsed.sources[0].source_type, SourceType::Journald);
+        assert_eq!(parsed.sources[0].units, ["nginx.service"]);
+
+        let result = config(&[r#"type = "journald""#, r#"priority = "loud""#]);
+        assert!(matches!(result, Err(ConfigError::InvalidPriority(_, _))));
+
+        let result = config(&[r#"type = "journald""#, "start_position = { offset = 10 }"]);
+        assert!(matches!(result, Err(ConfigError::InvalidJournalStart(_))));
+
+        // file sources still need a path
+        assert!(matches!(config(&[]), Err(ConfigError::MissingLogPath(_))));
+    }
+
+    #[test]
+    fn parse_source_format() {
+        let example = r#"
+            [global]
+            agent_name = "agent"
+            end_point = "http://localhost:8080/log"
+            send_type = "HTTP"
+
+            [[sources]]
+            name = "app1"
+            log_path = "app1.log"
+            format = "json"
+            timestamp = { field = "time" }
+
+            [[sources]]
+            name = "app2"
+            log_path = "app2.log"
+        "#;
+
+        let config = parse_config_from_toml(example).expect("parse err");
+
+        assert_eq!(config.sources[0].format, LogFormat::Json);
+        assert_eq!(config.sources[0].timestamp.as_ref().and_then(|t| t.field.as_deref()), Some("time"));
+        assert_eq!(config.sources[1].format, LogFormat::Raw);
+    }
+
+    #[test]
+    fn regex_format_is_must_be_valid() {
+        let example = r#"
+            [global]
+            agent_name = "agent"
+            end_point = "http://localhost:8080/log"
+            send_type = "HTTP"
+
+            [[sources]]
+            name = "app1"
+            log_path = "app1.log"
+            format = "regex"
+            pattern = "^(?P<level>[A-Z]+"
+        "#;
+
+        let result = parse_config_from_toml(example);
+        assert!(matches!(result, Err(ConfigError::InvalidParser(_, _))));
+    }
+
+    #[test]
+    fn filter_is_must_be_valid() {
+        let example = r#"
+            [global]
+            agent_name = "agent"
+            end_point = "http://localhost:8080/log"
+            send_type = "HTTP"
+
+            [[sources]]
+            name = "app1"
+            log_path = "app1.log"
+            exclude = ["healthz"]
+            min_level = "loud"
+        "#;
+
+        let result = parse_config_from_toml(example);
+        assert!(matches!(result, Err(ConfigError::InvalidFilter(_, _))));
+    }
+
+    #[test]
+    fn sampling_rate_is_must_be_in_range() {
+        let example = r#"
+            [global]
+            agent_name = "agent"
+            end_point = "http://localhost:8080/log"
+            send_type = "HTTP"
+
+            [[sources]]
+            name = "app1"
+            log_path = "app1.log"
+            sampling = { rate = 1.5 }
+        "#;
+
+        let result = parse_config_from_toml(example);
+        assert!(matches!(result, Err(ConfigError::InvalidSampleRate(_))));
+    }
+
+    #[test]
+    fn parse_multiple_outputs() {
+        let example = r#"
+            [global]
+            agent_name = "agent"
+
+            [[sources]]
+            name = "security-auth"
+            log_path = "auth.log"
+
+            [[outputs]]
+            name = "security"
+            end_point = "http://localhost:8080/security"
+            send_type = "HTTP"
+            sources = ["security-*"]
+            retry_count = 5
+
+            [[outputs]]
+            name = "mirror"
+            end_point = "http://localhost:9090/log"
+            send_type = "HTTP"
+        "#;
+
+        let config = parse_config_from_toml(example).expect("parse err");
+
+        assert_eq!(config.outputs.len(), 2);
+        assert_eq!(config.outputs[0].sources, vec!["security-*"]);
+        assert_eq!(config.outputs[0].retry_count, Some(5));
+        assert!(config.outputs[1].sources.is_empty());
+    }
+
+    #[test]
+    fn parse_failover_end_points() {
+        let example = r#"
+            [global]
+            agent_name = "agent"
+
+            [[sources]]
+            name = "app1"
+            log_path = "app1.log"
+
+            [[outputs]]
+            name = "collector"
+            end_point = "http://primary:8080/log"
+            send_type = "HTTP"
+            failover_end_points = ["http://secondary:8080/log"]
+            balance = "round_robin"
+        "#;
+
+        let config = parse_config_from_toml(example).expect("parse err");
+        let output = &config.outputs[0];
+
+        assert_eq!(output.end_points().count(), 2);
+        assert_eq!(output.balance, BalanceMode::RoundRobin);
+        assert_eq!(output.unhealthy_threshold, 3);
+    }
+
+    #[test]
+    fn parse_circuit_breaker() {
+        let example = r#"
+            [global]
+            agent_name = "agent"
+
+            [[sources]]
+            name = "app1"
+            log_path = "app1.log"
+
+            [[outputs]]
+            name = "collector"
+            end_point = "http://primary:8080/log"
+            send_type = "HTTP"
+
+            [outputs.circuit_breaker]
+            enabled = true
+            failure_threshold = 10
+        "#;
+
+        let config = parse_config_from_toml(example).expect("parse err");
+        let breaker = &config.outputs[0].circuit_breaker;
+
+        assert!(breaker.enabled);
+        assert_eq!(breaker.failure_threshold, 10);
+        assert_eq!(breaker.open_secs, 30);
+    }
+
+    #[test]
+    fn circuit_breaker_threshold_is_must_be_over_1() {
+        let example = r#"
+            [global]
+            agent_name = "agent"
+
+            [[sources]]
+            name = "app1"
+            log_path = "app1.log"
+
+            [[outputs]]
+            name = "collector"
+            end_point = "http://primary:8080/log"
+            send_type = "HTTP"
+            circuit_breaker = { enabled = true, failure_threshold = 0 }
+        "#;
+
+        let result = parse_config_from_toml(example);
+        assert!(matches!(result, Err(ConfigError::InvalidCircuitBreaker(_))));
+    }
+
+    #[test]
+    fn failover_end_point_is_must_be_valid() {
+        let example = r#"
+            [global]
+            agent_name = "agent"
+
+            [[sources]]
+            name = "app1"
+            log_path = "app1.log"
+
+            [[outputs]]
+            name = "collector"
+            end_point = "http://primary:8080/log"
+            send_type = "HTTP"
+            failover_end_points = ["ftp://secondary/log"]
+        "#;
+
+        let result = parse_config_from_toml(example);
+        assert!(matches!(result, Err(ConfigError::InvalidEndPoint(_))));
+    }
+
+    #[test]
+    fn end_point_or_outputs_is_required() {
+        let example = r#"
+            [global]
+            agent_name = "agent"
+
+            [[sources]]
+            name = "app1"
+            log_path = "app1.log"
+        "#;
+
+        let result = parse_config_from_toml(example);
+        assert!(matches!(result, Err(ConfigError::NoOutput)));
+    }
+
+    #[test]
+    fn end_point_with_outputs_is_rejected() {
+        let example = r#"
+            [global]
+            agent_name = "agent"
+            end_point = "http://localhost:8080/log"
+            send_type = "HTTP"
+
+            [[sources]]
+            name = "app1"
+            log_path = "app1.log"
+
+            [[outputs]]
+            name = "mirror"
+            end_point = "http://localhost:9090/log"
+            send_type = "HTTP"
+        "#;
+
+        let result = parse_config_from_toml(example);
+        assert!(matches!(result, Err(ConfigError::EndPointWithOutputs)));
+    }
+
+    #[test]
+    fn output_name_is_must_be_unique() {
+        let example = r#"
+            [global]
+            agent_name = "agent"
+
+            [[sources]]
+            name = "app1"
+            log_path = "app1.log"
+
+            [[outputs]]
+            name = "out"
+            end_point = "http://localhost:8080/log"
+            send_type = "HTTP"
+
+            [[outputs]]
+            name = "out"
+            end_point = "http://localhost:9090/log"
+            send_type = "HTTP"
+        "#;
+
+        let result = parse_config_from_toml(example);
+        assert!(matches!(result, Err(ConfigError::DuplicateOutputName(_))));
+    }
 }
@@ -0,0 +1,35 @@
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CircuitBreakerConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+
+    // consecutive failed sends before the circuit opens
+    #[serde(default = "default_failure_threshold")]
+    pub failure_threshold: u32,
+
+    // how long the circuit stays open before a probe is allowed
+    #[serde(default = "default_open_secs")]
+    pub open_secs: u64,
+
+    // concurrent probe sends while half-open, all must succeed to close again
+    #[serde(default = "default_half_open_probes")]
+    pub half_open_probes: u32,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            failure_threshold: default_failure_threshold(),
+            open_secs: default_open_secs(),
+            half_open_probes: default_half_open_probes(),
+        }
+    }
+}
+
+fn default_enabled() -> bool { false }
+fn default_failure_threshold() -> u32 { 5 }
+fn default_open_secs() -> u64 { 30 }
+fn default_half_open_probes() -> u32 { 1 }
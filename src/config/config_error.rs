@@ -1,3 +1,4 @@
+use crate::{filter::FilterError, parser::ParseError, processor::ProcessorError, redact::RedactError};
 use std::fmt::{Display, Formatter};
 use std::io;
 
@@ -12,6 +13,22 @@ pub enum ConfigError {
     ChannelBoundIsUnderOne,
     DuplicateSourceName(String),
     DuplicateLogPath(String),
+    MissingLogPath(String),
+    InvalidJournalStart(String),
+    InvalidPriority(String, String),
+    NoOutput,
+    EndPointWithOutputs,
+    DuplicateOutputName(String),
+    InvalidSourcePattern(String, glob::PatternError),
+    UnhealthyThresholdIsUnderOne,
+    InvalidCircuitBreaker(String),
+    InvalidParser(String, ParseError),
+    InvalidFilter(String, FilterError),
+    InvalidRedaction(String, RedactError),
+    RateLimitIsUnderOne(String),
+    InvalidSampleRate(String),
+    DedupWindowIsUnderOne(String),
+    InvalidPipeline(String, ProcessorError),
 }
 
 impl From<toml::de::Error> for ConfigError {
@@ -37,7 +54,23 @@ impl Display for ConfigError {
             ConfigError::RetryCountIsUnderOne => write!(f, "Retry count is must be over 1"),
             ConfigError::ChannelBoundIsUnderOne => write!(f, "Channel bound is must be over 1"),
             ConfigError::DuplicateSourceName(name) => write!(f, "Duplicated source name in config: '{name}'"),
-            ConfigError::DuplicateLogPath(path) => write!(f, "Duplicated log file path in config: '{path}'")
+            ConfigError::DuplicateLogPath(path) => write!(f, "Duplicated log file path in config: '{path}'"),
+            ConfigError::MissingLogPath(name) => write!(f, "File source '{name}' needs log_path"),
+            ConfigError::InvalidJournalStart(name) => write!(f, "Journald source '{name}' cannot start at a byte offset"),
+            ConfigError::InvalidPriority(name, priority) => write!(f, "Unknown journal priority '{priority}' of source '{name}'"),
+            ConfigError::NoOutput => write!(f, "No output configured: set global end_point/send_type or add [[outputs]]"),
+            ConfigError::EndPointWithOutputs => write!(f, "global end_point/send_type cannot be used together with [[outputs]]"),
+            ConfigError::DuplicateOutputName(name) => write!(f, "Duplicated output name in config: '{name}'"),
+            ConfigError::InvalidSourcePattern(pattern, e) => write!(f, "Invalid source pattern '{pattern}': {e}"),
+            ConfigError::UnhealthyThresholdIsUnderOne => write!(f, "Unhealthy threshold is must be over 1"),
+            ConfigError::InvalidCircuitBreaker(name) => write!(f, "Circuit breaker thresholds of output '{name}' are must be over 1"),
+            ConfigError::InvalidParser(name, e) => write!(f, "Invalid format of source '{name}': {e}"),
+            ConfigError::InvalidFilter(name, e) => write!(f, "Invalid filter of source '{name}': {e}"),
+            ConfigError::InvalidRedaction(name, e) => write!(f, "Invalid redaction of source '{name}': {e}"),
+            ConfigError::RateLimitIsUnderOne(name) => write!(f, "Rate limit of source '{name}' is must be over 1"),
+            ConfigError::InvalidSampleRate(name) => write!(f, "Sampling rate of source '{name}' is must be over 0 and at most 1"),
+            ConfigError::DedupWindowIsUnderOne(name) => write!(f, "Dedup window of source '{name}' is must be over 1"),
+            ConfigError::InvalidPipeline(name, e) => write!(f, "Invalid processors of source '{name}': {e}"),
         }
     }
 }
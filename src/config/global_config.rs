@@ -1,6 +1,11 @@
 use serde::Deserialize;
+use std::{collections::BTreeMap, net::SocketAddr};
 
-#[derive(Debug, Deserialize)]
+// static key/value pairs attached to payloads, sorted for a stable output
+pub type Labels = BTreeMap<String, String>;
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, Deserialize)]
 pub enum SendType {
     HTTP,
 }
@@ -8,8 +13,10 @@ pub enum SendType {
 #[derive(Debug, Deserialize)]
 pub struct GlobalConfig {
     pub agent_name: String,
-    pub end_point: String,
-    pub send_type: SendType,
+
+    // single output shorthand, used when no [[outputs]] are configured
+    pub end_point: Option<String>,
+    pub send_type: Option<SendType>,
 
     #[serde(default = "default_max_send_task")]
     pub max_send_task: u8,
@@ -28,6 +35,19 @@ pub struct GlobalConfig {
 
     #[serde(default = "default_max_batch_size")]
     pub max_batch_size: u8,
+
+    // reported hostname, the OS hostname when omitted
+    pub hostname: Option<String>,
+
+    #[serde(default)]
+    pub labels: Labels,
+
+    // prometheus metrics and the /healthz and /readyz checks are served on this address,
+    // without it there are no health checks either
+    pub metrics_addr: Option<SocketAddr>,
+
+    // delivered offsets are saved here so a restart resumes where it stopped
+    pub checkpoint_path: Option<String>,
 }
 
 fn default_max_send_task() -> u8 { 5 }
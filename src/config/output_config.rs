@@ -0,0 +1,93 @@
+use serde::Deserialize;
+
+use super::{CircuitBreakerConfig, SendType};
+
+pub const DEFAULT_OUTPUT_NAME: &str = "default";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BalanceMode {
+    // always prefer the first healthy endpoint in order
+    #[default]
+    Failover,
+    // rotate across every healthy endpoint
+    RoundRobin,
+}
+
+// what happens to a payload when the queue of this output is full
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+    // wait for room, which holds back the payloads of every other output too
+    #[default]
+    Block,
+    // fail the payload for this output only, the other outputs keep receiving
+    Drop,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct OutputConfig {
+    pub name: String,
+    pub end_point: String,
+    pub send_type: SendType,
+
+    // tried in order after end_point when it is unhealthy
+    #[serde(default)]
+    pub failover_end_points: Vec<String>,
+
+    #[serde(default)]
+    pub balance: BalanceMode,
+
+    // consecutive retryable failures before an endpoint is marked unhealthy
+    #[serde(default = "default_unhealthy_threshold")]
+    pub unhealthy_threshold: u32,
+
+    #[serde(default = "default_unhealthy_cooldown_secs")]
+    pub unhealthy_cooldown_secs: u64,
+
+    #[serde(default)]
+    pub circuit_breaker: CircuitBreakerConfig,
+
+    // payloads buffered while the circuit is open, oldest are dropped first
+    #[serde(default = "default_max_spool_size")]
+    pub max_spool_size: usize,
+
+    // source names or glob patterns (e.g. "security-*"), empty routes every source
+    #[serde(default)]
+    pub sources: Vec<String>,
+
+    #[serde(default)]
+    pub overflow: OverflowPolicy,
+
+    // fall back to the global retry settings when omitted
+    pub retry_count: Option<u8>,
+    pub retry_delay_ms: Option<u64>,
+}
+
+impl OutputConfig {
+    pub fn new(name: String, end_point: String, send_type: SendType) -> Self {
+        Self {
+            name,
+            end_point,
+            send_type,
+            failover_end_points: Vec::new(),
+            balance: BalanceMode::default(),
+            unhealthy_threshold: default_unhealthy_threshold(),
+            unhealthy_cooldown_secs: default_unhealthy_cooldown_secs(),
+            circuit_breaker: CircuitBreakerConfig::default(),
+            max_spool_size: default_max_spool_size(),
+            sources: Vec::new(),
+            overflow: OverflowPolicy::default(),
+            retry_count: None,
+            retry_delay_ms: None,
+        }
+    }
+
+    pub fn end_points(&self) -> impl Iterator<Item = &String> {
+        std::iter::once(&self.end_point).chain(self.failover_end_points.iter())
+    }
+}
+
+fn default_unhealthy_threshold() -> u32 { 3 }
+fn default_unhealthy_cooldown_secs() -> u64 { 30 }
+fn default_max_spool_size() -> usize { 1000 }
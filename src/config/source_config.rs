@@ -1,12 +1,228 @@
 use serde::Deserialize;
 
-#[derive(Debug, Deserialize)]
+use super::Labels;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SourceType {
+    // tails `log_path`
+    #[default]
+    File,
+    // follows the systemd journal through `journalctl`
+    Journald,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    // send the line as is
+    #[default]
+    Raw,
+    // one JSON object per line
+    Json,
+    // named capture groups of `pattern` or the built-in `pattern_name`
+    Regex,
+    // key=value pairs
+    Logfmt,
+}
+
+// where reading starts when no checkpoint applies
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StartPosition {
+    // only lines written after startup
+    #[default]
+    End,
+    Beginning,
+    // byte offset, a line it points into is skipped
+    Offset(u64),
+    LastLines(u64),
+}
+
+// where the time a log was written is taken from, exactly one of field or pattern
+#[derive(Debug, Clone, Deserialize)]
+pub struct TimestampConfig {
+    // parsed field holding the timestamp
+    pub field: Option<String>,
+    // regex on the raw line, uses the group named "timestamp" or the first group
+    pub pattern: Option<String>,
+    // strftime format, RFC 3339 / common formats / unix epoch are detected when omitted
+    pub format: Option<String>,
+    // applies when the value has no offset: "UTC", "Local", "+09:00" or "Asia/Seoul"
+    pub timezone: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RedactBuiltin {
+    // 13 to 19 digits passing the Luhn check, spaces and dashes allowed
+    CreditCard,
+    Email,
+    Jwt,
+    // IPv4 and IPv6 addresses
+    Ip,
+}
+
+// masks matches in the line and in string fields, exactly one of builtin or pattern
+#[derive(Debug, Clone, Deserialize)]
+pub struct RedactRule {
+    pub builtin: Option<RedactBuiltin>,
+    pub pattern: Option<String>,
+    // may refer to capture groups of `pattern` like `${1}`
+    #[serde(default = "default_replacement")]
+    pub replacement: String,
+    // replace with a salted sha256 prefix instead, keeps equal values correlatable
+    #[serde(default)]
+    pub hash: bool,
+    #[serde(default)]
+    pub salt: String,
+}
+
+// token buckets refilled every second, a burst of one second is allowed
+#[derive(Debug, Clone, Deserialize)]
+pub struct RateLimitConfig {
+    pub lines_per_sec: Option<u64>,
+    pub bytes_per_sec: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SampleMode {
+    #[default]
+    Random,
+    // the same key is always kept or always dropped, on every agent
+    Hash,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SamplingConfig {
+    // fraction of lines to keep, 0 < rate <= 1
+    pub rate: f64,
+    #[serde(default)]
+    pub mode: SampleMode,
+    // parsed field hashed in hash mode, the whole line when omitted
+    pub key: Option<String>,
+}
+
+// collapses consecutive identical lines into one event with a repeat count
+#[derive(Debug, Clone, Deserialize)]
+pub struct DedupConfig {
+    // how long a run of repeated lines is held from its first line
+    #[serde(default = "default_dedup_window_secs")]
+    pub window_secs: u64,
+    // lines differing only in numbers count as identical
+    #[serde(default)]
+    pub normalize_numbers: bool,
+}
+
+// one step of the per-source pipeline, built-in steps use the options of the source
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ProcessorConfig {
+    // `format`, `pattern`, `pattern_name` and `timestamp`
+    Parse,
+    // `include` and `exclude`
+    Filter,
+    // `min_level`
+    LevelFilter,
+    // `rate_limit`
+    RateLimit,
+    // `sampling`
+    Sample,
+    // `redact`
+    Redact,
+    // one event per element of a parsed array field
+    Explode { field: String },
+    // Rhai script from `path` or inline `script`
+    Script {
+        path: Option<String>,
+        script: Option<String>,
+        // instruction budget of one call, endless loops fail instead of blocking the source
+        #[serde(default = "default_max_operations")]
+        max_operations: u64,
+    },
+    // WebAssembly module, reloaded when the file changes
+    Wasm {
+        path: String,
+        // instruction budget of one call
+        #[serde(default = "default_fuel")]
+        fuel: u64,
+        #[serde(default = "default_max_memory_mb")]
+        max_memory_mb: usize,
+    },
+}
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct SourceConfig {
     pub name: String,
+
+    #[serde(rename = "type", default)]
+    pub source_type: SourceType,
+
+    // required for file sources
+    #[serde(default)]
     pub log_path: String,
 
+    // journald only: entries of any of these units, every unit when empty
+    #[serde(default)]
+    pub units: Vec<String>,
+    // journald only: entries up to this priority, a name like "warning" or 0 to 7
+    pub priority: Option<String>,
+    // journald only: read the journal files of this directory instead of the system journal
+    pub journal_dir: Option<String>,
+
     #[serde(default = "default_delay_ms")]
     pub delay_ms: u64,
+
+    // fail startup when the file is missing instead of waiting for it
+    #[serde(default)]
+    pub required: bool,
+
+    #[serde(default)]
+    pub start_position: StartPosition,
+
+    #[serde(default)]
+    pub format: LogFormat,
+
+    pub pattern: Option<String>,
+    pub pattern_name: Option<String>,
+
+    // keep the raw line in `data` once it was parsed into fields
+    #[serde(default = "default_keep_original")]
+    pub keep_original: bool,
+
+    pub timestamp: Option<TimestampConfig>,
+
+    // regexes on the raw line, a line must match one include (if any) and no exclude
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+
+    // drop parsed lines below this level, lines without a known level are kept
+    pub min_level: Option<String>,
+    #[serde(default = "default_level_field")]
+    pub level_field: String,
+
+    #[serde(default)]
+    pub redact: Vec<RedactRule>,
+
+    #[serde(default)]
+    pub labels: Labels,
+
+    pub rate_limit: Option<RateLimitConfig>,
+    pub sampling: Option<SamplingConfig>,
+    pub dedup: Option<DedupConfig>,
+
+    // order of processors, every configured built-in when omitted
+    pub processors: Option<Vec<ProcessorConfig>>,
 }
 
 fn default_delay_ms() -> u64 { 500 }
+fn default_keep_original() -> bool { true }
+fn default_replacement() -> String { "[REDACTED]".to_string() }
+fn default_max_operations() -> u64 { 100_000 }
+fn default_fuel() -> u64 { 1_000_000 }
+fn default_max_memory_mb() -> usize { 16 }
+fn default_dedup_window_secs() -> u64 { 10 }
+fn default_level_field() -> String { "level".to_string() }
@@ -0,0 +1,234 @@
+use regex::Regex;
+use std::{
+    collections::HashMap,
+    mem,
+    time::{Duration, Instant},
+};
+
+use crate::{config::SourceConfig, log_event::LogEvent};
+
+struct DedupRule {
+    window: Duration,
+    normalize_numbers: bool,
+}
+
+// the latest line of a source, held back until a different line arrives or the window ends
+struct Run {
+    key: String,
+    event: LogEvent,
+    started: Instant,
+}
+
+// collapses consecutive identical lines per source before they are batched
+pub struct Deduplicator {
+    rules: HashMap<String, DedupRule>,
+    runs: HashMap<String, Run>,
+    numbers: Regex,
+}
+
+impl Deduplicator {
+    pub fn build(sources: &[SourceConfig]) -> Self {
+        let rules = sources
+            .iter()
+            .filter_map(|source| {
+                source.dedup.as_ref().map(|dedup| {
+                    let rule = DedupRule {
+                        window: Duration::from_secs(dedup.window_secs),
+                        normalize_numbers: dedup.normalize_numbers,
+                    };
+                    (source.name.clone(), rule)
+                })
+            })
+            .collect();
+
+        Self {
+            rules,
+            runs: HashMap::new(),
+            numbers: Regex::new(r"\d+").expect("valid regex"),
+        }
+    }
+
+    // returns the event ready to be batched, if any
+    pub fn push(&mut self, event: LogEvent) -> Option<LogEvent> {
+        self.push_at(event, Instant::now())
+    }
+
+    // runs whose window has ended
+    pub fn expire(&mut self) -> Vec<LogEvent> {
+        self.expire_at(Instant::now())
+    }
+
+    pub fn drain(&mut self) -> Vec<LogEvent> {
+        self.runs.drain().map(|(_, run)| run.event).collect()
+    }
+
+    fn push_at(&mut self, event: LogEvent, now: Instant) -> Option<LogEvent> {
+        let Some(rule) = self.rules.get(&event.name) else { return Some(event) };
+
+        // the line is empty when only fields are kept
+        let key = if event.log.is_empty() {
+            serde_json::to_string(&event.fields).unwrap_or_default()
+        } else {
+            event.log.clone()
+        };
+        let key = match rule.normalize_numbers {
+            true => self.numbers.replace_all(&key, "0").into_owned(),
+            false => key,
+        };
+
+        if let Some(run) = self.runs.get_mut(&event.name) {
+            if run.key == key && now.saturating_duration_since(run.started) < rule.window {
+                run.event.repeat_count += 1;
+                // the delivered checkpoint has to cover the collapsed lines too, the latest
+                // line may be in a rotated file or carry a newer journal cursor
+                run.event.offset = event.offset;
+                run.event.cursor = event.cursor;
+                run.event.meta = event.meta;
+                return None;
+            }
+        }
+
+        let run = Run { key, event, started: now };
+        match self.runs.get_mut(&run.event.name) {
+            Some(previous) => Some(mem::replace(previous, run).event),
+            None => {
+                self.runs.insert(run.event.name.clone(), run);
+                None
+            }
+        }
+    }
+
+    fn expire_at(&mut self, now: Instant) -> Vec<LogEvent> {
+        let expired: Vec<String> = self
+            .runs
+            .iter()
+            .filter(|(name, run)| {
+                self.rules
+                    .get(*name)
+                    .is_none_or(|rule| now.saturating_duration_since(run.started) >= rule.window)
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+
+        expired
+            .into_iter()
+            .filter_map(|name| self.runs.remove(&name))
+            .map(|run| run.event)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log_event::SourceMeta;
+    use std::sync::Arc;
+
+    fn deduplicator(dedup: &str) -> Deduplicator {
+        let sources: Vec<SourceConfig> = vec![
+            toml::from_str(&format!(
+                r#"
+                    name = "app1"
+                    log_path = "app1.log"
+                    dedup = {dedup}
+                "#
+            ))
+            .unwrap(),
+            toml::from_str(
+                r#"
+                    name = "app2"
+                    log_path = "app2.log"
+                "#,
+            )
+            .unwrap(),
+        ];
+
+        Deduplicator::build(&sources)
+    }
+
+    fn event(name: &str, line: &str, offset: u64) -> LogEvent {
+        LogEvent::new(name.to_string(), line.to_string(), offset)
+    }
+
+    #[test]
+    fn collapses_consecutive_identical_lines() {
+        let mut dedup = deduplicator("{}");
+        let now = Instant::now();
+
+        assert!(dedup.push_at(event("app1", "db down", 10), now).is_none());
+        assert!(dedup.push_at(event("app1", "db down", 20), now).is_none());
+        assert!(dedup.push_at(event("app1", "db down", 30), now).is_none());
+
+        let collapsed = dedup.push_at(event("app1", "recovered", 40), now).unwrap();
+        assert_eq!(collapsed.log, "db down");
+        assert_eq!(collapsed.repeat_count, 3);
+        assert_eq!(collapsed.offset, 30);
+
+        let last = dedup.drain();
+        assert_eq!(last.len(), 1);
+        assert_eq!(last[0].repeat_count, 1);
+    }
+
+    #[test]
+    fn collapsed_run_checkpoints_its_latest_line() {
+        let mut dedup = deduplicator("{}");
+        let now = Instant::now();
+        let line = |offset: u64, cursor: &str, inode: u64| {
+            let mut event = event("app1", "db down", offset);
+            event.cursor = Some(cursor.to_string());
+            event.meta = Some(Arc::new(SourceMeta { path: "app1.log".to_string(), inode, labels: Default::default() }));
+            event
+        };
+
+        dedup.push_at(line(10, "s=1;i=1", 1), now);
+        dedup.push_at(line(20, "s=1;i=2", 1), now);
+        dedup.push_at(line(5, "s=1;i=3", 2), now);
+
+        let collapsed = dedup.drain().remove(0);
+        assert_eq!(collapsed.repeat_count, 3);
+        assert_eq!(collapsed.offset, 5);
+        assert_eq!(collapsed.cursor.as_deref(), Some("s=1;i=3"));
+        assert_eq!(collapsed.meta.unwrap().inode, 2);
+    }
+
+    #[test]
+    fn normalizes_numbers() {
+        let mut dedup = deduplicator("{ normalize_numbers = true }");
+        let now = Instant::now();
+
+        dedup.push_at(event("app1", "retry 1 failed after 120ms", 10), now);
+        dedup.push_at(event("app1", "retry 2 failed after 95ms", 20), now);
+
+        let collapsed = dedup.drain().remove(0);
+        assert_eq!(collapsed.log, "retry 1 failed after 120ms");
+        assert_eq!(collapsed.repeat_count, 2);
+    }
+
+    #[test]
+    fn window_ends_run() {
+        let mut dedup = deduplicator("{ window_secs = 10 }");
+        let now = Instant::now();
+
+        dedup.push_at(event("app1", "db down", 10), now);
+        dedup.push_at(event("app1", "db down", 20), now + Duration::from_secs(5));
+        assert!(dedup.expire_at(now + Duration::from_secs(9)).is_empty());
+
+        let expired = dedup.expire_at(now + Duration::from_secs(10));
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].repeat_count, 2);
+
+        // the same line after the window starts a new run
+        dedup.push_at(event("app1", "db down", 30), now + Duration::from_secs(11));
+        assert_eq!(dedup.drain()[0].repeat_count, 1);
+    }
+
+    #[test]
+    fn sources_without_dedup_pass_through() {
+        let mut dedup = deduplicator("{}");
+        let now = Instant::now();
+
+        assert!(dedup.push_at(event("app2", "db down", 10), now).is_some());
+        assert!(dedup.push_at(event("app2", "db down", 20), now).is_some());
+        assert!(dedup.drain().is_empty());
+    }
+}
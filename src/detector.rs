@@ -1,12 +1,21 @@
-use crate::{config::SourceConfig, log_event::LogEvent};
+use crate::{
+    checkpoint::{Checkpoint, CheckpointStore},
+    config::{SourceConfig, SourceType, StartPosition},
+    health::health,
+    log_event::{LogEvent, SourceMeta},
+    metrics::{metrics, SourceMetrics},
+    processor::{Pipeline, SourceStage},
+};
 use std::{
-    fs::{metadata, File},
-    io::{BufRead, BufReader, Seek, SeekFrom},
-    mem, thread,
+    fs::{self, metadata, File},
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom},
+    mem,
+    sync::{atomic::Ordering, Arc},
+    thread,
     time::Duration,
 };
 use tokio::sync::mpsc::Sender;
-use tracing::{error, info, trace, warn};
+use tracing::{info, trace, warn};
 
 pub mod error;
 pub use error::DetectError;
@@ -14,81 +23,125 @@ pub use error::DetectError;
 mod event;
 use event::DetectEvent;
 
+mod journald;
+pub use journald::journal_priority;
+use journald::JournalDetector;
+
+mod supervisor;
+use supervisor::Supervisor;
+
+// what a supervisor keeps running for one source
+pub trait Detect: Send {
+    // reads until the event channel is closed or reading fails
+    fn detect(&mut self) -> Result<(), DetectError>;
+
+    // where a restarted detector continues
+    fn resume_point(&self) -> ReadFrom;
+}
+
 pub struct Detector {
     source: SourceConfig,
     current_len: u64,
+    // byte offset of the next line to read
+    position: u64,
+    // lines of the current file before `position`
+    line_number: u64,
+    meta: Arc<SourceMeta>,
     reader: BufReader<File>,
     buf: String,
-    event_sender: Sender<LogEvent>,
+    stage: SourceStage,
+    metrics: Arc<SourceMetrics>,
+}
+
+// where a detector starts reading its file
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReadFrom {
+    Position(StartPosition),
+    // a checkpoint or where a failed detector stopped, only used while the file keeps this inode
+    Resume(Checkpoint),
 }
 
 impl Detector {
     pub fn build(
         source: SourceConfig,
-        event_sender: Sender<LogEvent>,
+        stage: SourceStage,
+        read_from: ReadFrom,
     ) -> Result<Self, DetectError> {
-        let (reader, current_len) = Self::open_reader_at_end(&source.log_path)?;
+        let opened = Self::open_reader(&source, read_from)?;
 
         Ok(Self {
+            metrics: metrics().source(&source.name),
             source,
-            current_len,
-            reader,
-            event_sender,
+            current_len: opened.len,
+            position: opened.position,
+            line_number: opened.line_number,
+            meta: opened.meta,
+            reader: opened.reader,
+            stage,
             buf: String::with_capacity(1024),
         })
     }
 
-    pub fn detect(&mut self) -> Result<(), DetectError> {
-        info!("[{}] Started detecting ", self.source.name);
-        let watching_delay = Duration::from_millis(self.source.delay_ms);
-
-        loop {
-            match self.next_event() {
-                Ok(DetectEvent::NewLine(line)) => self.handle_newline(line)?,
-                Ok(DetectEvent::Rotated) => self.handle_rotate()?,
-                Ok(DetectEvent::EndOfFile) => thread::sleep(watching_delay),
-                Err(e) => match e {
-                    DetectError::Recoverable(e) => {
-                        warn!("[{}] {e}", self.source.name);
-                        thread::sleep(watching_delay);
-                        continue;
-                    }
-                    DetectError::UnRecoverable(_) | DetectError::ChannelClosed(_) => {
-                        return Err(e);
-                    }
-                },
-            }
-        }
-    }
-
+    // the pipeline of the source runs here, dropped lines never reach the shared channel
     fn handle_newline(&self, log: String) -> Result<(), DetectError> {
         if log.is_empty() { return Ok(()); }
         trace!("[{}] detected new line", &self.source.name);
 
-        self.event_sender.blocking_send(LogEvent::new(self.source.name.clone(), log))?;
+        let mut event = LogEvent::new(self.source.name.clone(), log, self.position);
+        event.meta = Some(self.meta.clone());
+        event.line_number = self.line_number;
+
+        self.stage.send(event)?;
 
         Ok(())
     }
 
     fn handle_rotate(&mut self) -> Result<(), DetectError> {
         info!("[{}] is rotated", self.source.name);
-        let (reader, current_len) = Self::open_reader_at_end(&self.source.log_path)?;
+        self.metrics.rotations.fetch_add(1, Ordering::Relaxed);
+        // everything in the new file was written after the rotation
+        let opened = Self::open_reader(&self.source, ReadFrom::Position(StartPosition::Beginning))?;
 
-        self.reader = reader;
-        self.current_len = current_len;
+        self.reader = opened.reader;
+        self.current_len = opened.len;
+        self.position = opened.position;
+        self.line_number = opened.line_number;
+        self.meta = opened.meta;
 
         Ok(())
     }
 
-    fn open_reader_at_end(path: &str) -> Result<(BufReader<File>, u64), DetectError> {
-        let file = File::open(path)?;
-        let meta = file.metadata()?;
-        let current_len = meta.len();
+    // seeks to the start, the lines before it are counted once so line numbers stay exact
+    fn open_reader(source: &SourceConfig, read_from: ReadFrom) -> Result<OpenedFile, DetectError> {
+        let file = File::open(&source.log_path)?;
+        let file_meta = file.metadata()?;
+        let len = file_meta.len();
+        let meta = Arc::new(SourceMeta {
+            path: fs::canonicalize(&source.log_path)?.to_string_lossy().into_owned(),
+            inode: inode(&file_meta),
+            labels: source.labels.clone(),
+        });
+
+        let start = match read_from {
+            // a checkpoint of a rotated or truncated file does not apply, the new content is all unread
+            ReadFrom::Resume(checkpoint) if checkpoint.inode != meta.inode || checkpoint.offset > len => {
+                info!("[{}] checkpoint does not match {}, reading from the beginning", source.name, meta.path);
+                StartPosition::Beginning
+            }
+            ReadFrom::Resume(checkpoint) => StartPosition::Offset(checkpoint.offset),
+            ReadFrom::Position(position) => position,
+        };
 
-        let mut reader = BufReader::new(file);
-        reader.seek(SeekFrom::End(0))?;
+        let mut reader = BufReader::with_capacity(64 * 1024, file);
+        let position = match start {
+            StartPosition::End => reader.seek(SeekFrom::End(0))?,
+            StartPosition::Beginning => 0,
+            StartPosition::Offset(offset) => seek_to_line(&mut reader, offset.min(len))?,
+            StartPosition::LastLines(count) => seek_last_lines(&mut reader, len, count)?,
+        };
+        let line_number = count_lines(&mut reader, position)?;
 
-        Ok((reader, current_len))
+        Ok(OpenedFile { reader, len, position, line_number, meta })
     }
 
     fn next_event(&mut self) -> Result<DetectEvent, DetectError> {
@@ -105,6 +158,11 @@ impl Detector {
             return Ok(DetectEvent::EndOfFile);
         }
 
+        self.position += bytes as u64;
+        self.line_number += 1;
+        self.metrics.lines_read.fetch_add(1, Ordering::Relaxed);
+        self.metrics.bytes_read.fetch_add(bytes as u64, Ordering::Relaxed);
+
         while matches!(self.buf.as_bytes().last(), Some(b'\n' | b'\r')) {
             self.buf.pop();
         }
@@ -114,21 +172,166 @@ impl Detector {
     }
 }
 
-pub fn spawn_detectors(event_sender: Sender<LogEvent>, sources: Vec<SourceConfig>) -> Result<Vec<thread::JoinHandle<()>>, DetectError> {
+impl Detect for Detector {
+    fn detect(&mut self) -> Result<(), DetectError> {
+        info!("[{}] Started detecting ", self.source.name);
+        let watching_delay = Duration::from_millis(self.source.delay_ms);
+
+        loop {
+            match self.next_event() {
+                Ok(DetectEvent::NewLine(line)) => self.handle_newline(line)?,
+                Ok(DetectEvent::Rotated) => self.handle_rotate()?,
+                Ok(DetectEvent::EndOfFile) => {
+                    self.stage.flush()?;
+                    thread::sleep(watching_delay);
+                }
+                Err(e) => match e {
+                    DetectError::Recoverable(e) => {
+                        warn!("[{}] {e}", self.source.name);
+                        thread::sleep(watching_delay);
+                        continue;
+                    }
+                    DetectError::UnRecoverable(_) | DetectError::ChannelClosed(_) | DetectError::InvalidPipeline(_) => {
+                        return Err(e);
+                    }
+                },
+            }
+        }
+    }
+
+    fn resume_point(&self) -> ReadFrom {
+        ReadFrom::Resume(Checkpoint { inode: self.meta.inode, offset: self.position, cursor: None })
+    }
+}
+
+pub fn build_detector(
+    source: SourceConfig,
+    stage: SourceStage,
+    read_from: ReadFrom,
+) -> Result<Box<dyn Detect>, DetectError> {
+    Ok(match source.source_type {
+        SourceType::File => Box::new(Detector::build(source, stage, read_from)?),
+        SourceType::Journald => Box::new(JournalDetector::build(source, stage, read_from)?),
+    })
+}
+
+struct OpenedFile {
+    reader: BufReader<File>,
+    len: u64,
+    position: u64,
+    line_number: u64,
+    meta: Arc<SourceMeta>,
+}
+
+// newlines before `position`, the reader is left at `position`
+fn count_lines(reader: &mut BufReader<File>, position: u64) -> io::Result<u64> {
+    if position == 0 {
+        return Ok(0);
+    }
+
+    let mut chunk = vec![0; 64 * 1024];
+    let mut read = 0;
+    let mut lines = 0;
+    reader.seek(SeekFrom::Start(0))?;
+
+    while read < position {
+        let chunk = &mut chunk[..(position - read).min(64 * 1024) as usize];
+        reader.read_exact(chunk)?;
+        lines += chunk.iter().filter(|&&byte| byte == b'\n').count() as u64;
+        read += chunk.len() as u64;
+    }
+
+    Ok(lines)
+}
+
+// seeks to `offset`, a line it points into is skipped
+fn seek_to_line(reader: &mut BufReader<File>, offset: u64) -> io::Result<u64> {
+    if offset == 0 {
+        return Ok(0);
+    }
+
+    let mut previous = [0; 1];
+    reader.seek(SeekFrom::Start(offset - 1))?;
+    reader.read_exact(&mut previous)?;
+    if previous == *b"\n" {
+        return Ok(offset);
+    }
+
+    let mut rest = Vec::new();
+    let skipped = reader.read_until(b'\n', &mut rest)?;
+    Ok(offset + skipped as u64)
+}
+
+// seeks to the start of the last `count` complete lines, the file is read backwards from its end
+fn seek_last_lines(reader: &mut BufReader<File>, len: u64, count: u64) -> io::Result<u64> {
+    let mut chunk = vec![0; 64 * 1024];
+    let mut end = len;
+    let mut newlines = 0;
+
+    let position = 'search: loop {
+        if end == 0 {
+            break 0;
+        }
+
+        let start = end.saturating_sub(chunk.len() as u64);
+        let chunk = &mut chunk[..(end - start) as usize];
+        reader.seek(SeekFrom::Start(start))?;
+        reader.read_exact(chunk)?;
+
+        for (i, &byte) in chunk.iter().enumerate().rev() {
+            if byte == b'\n' {
+                newlines += 1;
+                // the newline ending the line before the first one read
+                if newlines > count {
+                    break 'search start + i as u64 + 1;
+                }
+            }
+        }
+
+        end = start;
+    };
+
+    reader.seek(SeekFrom::Start(position))?;
+    Ok(position)
+}
+
+#[cfg(unix)]
+pub fn inode(meta: &fs::Metadata) -> u64 {
+    std::os::unix::fs::MetadataExt::ino(meta)
+}
+
+#[cfg(not(unix))]
+pub fn inode(_meta: &fs::Metadata) -> u64 {
+    0
+}
+
+pub fn spawn_detectors(
+    event_sender: Sender<LogEvent>,
+    sources: Vec<SourceConfig>,
+    checkpoints: Option<&CheckpointStore>,
+) -> Result<Vec<thread::JoinHandle<()>>, DetectError> {
     let mut detector_handles: Vec<thread::JoinHandle<()>> = Vec::new();
 
     for source in sources {
         let thread_name = format!("detector-thread-{}", source.name);
-        let event_sender = event_sender.clone();
-        let mut detector = Detector::build(source, event_sender)?;
+        let stage = SourceStage::new(Pipeline::build(&source)?, event_sender.clone());
+        let read_from = match checkpoints.and_then(|checkpoints| checkpoints.get(&source.name)) {
+            Some(checkpoint) => ReadFrom::Resume(checkpoint),
+            None => ReadFrom::Position(source.start_position),
+        };
+
+        // a missing file is waited for by the supervisor unless it is required
+        let detector = match build_detector(source.clone(), stage.clone(), read_from) {
+            Err(e) if e.is_file_missing() && !source.required => Err(e),
+            result => Ok(result?),
+        };
+        let source_health = health().source(&source.name);
+        let source_metrics = metrics().source(&source.name);
+        let supervisor = Supervisor::new(source, stage, source_health, source_metrics);
 
         let detector_handle = thread::Builder::new()
             .name(thread_name)
-            .spawn(move || {
-                if let Err(e) = detector.detect() {
-                    error!("Detecting error: {e}");
-                }
-            })?;
+            .spawn(move || supervisor.run(detector))?;
 
         detector_handles.push(detector_handle);
     }
@@ -137,4 +340,133 @@ pub fn spawn_detectors(event_sender: Sender<LogEvent>, sources: Vec<SourceConfig
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::*;
+    use crate::test_util::TempPath;
+    use std::io::Write;
+    use tokio::sync::mpsc;
+
+    #[test]
+    fn events_carry_file_meta_and_line_number() {
+        let path = TempPath::new("detector.log");
+        fs::write(&path, "first\nsecond\n").unwrap();
+
+        let source: SourceConfig = toml::from_str(&format!(
+            r#"
+                name = "app1"
+                log_path = "{}"
+                labels = {{ team = "core" }}
+            "#,
+            path.display()
+        ))
+        .unwrap();
+
+        let (event_sender, mut event_receiver) = mpsc::channel(8);
+        let stage = SourceStage::new(Pipeline::build(&source).unwrap(), event_sender);
+        let mut detector = Detector::build(source, stage, ReadFrom::Position(StartPosition::Beginning)).unwrap();
+        detector.next_event().unwrap();
+        detector.next_event().unwrap();
+
+        fs::OpenOptions::new().append(true).open(&path).unwrap().write_all(b"third\n").unwrap();
+        match detector.next_event().unwrap() {
+            DetectEvent::NewLine(line) => detector.handle_newline(line).unwrap(),
+            _ => panic!("expected a new line"),
+        }
+
+        let event = event_receiver.try_recv().unwrap();
+        let meta = event.meta.unwrap();
+        assert_eq!(event.log, "third");
+        assert_eq!(event.line_number, 3);
+        assert_eq!(event.offset, 19);
+        assert_eq!(meta.path, fs::canonicalize(&path).unwrap().to_string_lossy());
+        assert_eq!(meta.labels.get("team").map(String::as_str), Some("core"));
+    }
+
+    #[test]
+    fn missing_file_is_fatal_only_when_required() {
+        let path = TempPath::new("detector-missing.log");
+        let source = |required: bool| -> SourceConfig {
+            toml::from_str(&format!(
+                r#"
+                    name = "missing"
+                    log_path = "{}"
+                    required = {required}
+                "#,
+                path.display()
+            ))
+            .unwrap()
+        };
+
+        let (event_sender, event_receiver) = mpsc::channel(8);
+        assert!(spawn_detectors(event_sender.clone(), vec![source(true)], None).is_err_and(|e| e.is_file_missing()));
+
+        let handles = spawn_detectors(event_sender, vec![source(false)], None).unwrap();
+
+        // closing the channel stops the supervisor waiting for the file
+        drop(event_receiver);
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+
+    fn source_at(path: &std::path::Path) -> SourceConfig {
+        toml::from_str(&format!(
+            r#"
+                name = "app1"
+                log_path = "{}"
+            "#,
+            path.display()
+        ))
+        .unwrap()
+    }
+
+    fn opened_at(content: &str, read_from: ReadFrom) -> (u64, u64) {
+        let path = TempPath::new("start.log");
+        fs::write(&path, content).unwrap();
+
+        let opened = Detector::open_reader(&source_at(&path), read_from).unwrap();
+        (opened.position, opened.line_number)
+    }
+
+    #[test]
+    fn start_positions() {
+        let content = "one\ntwo\nthree\n";
+
+        // the line number counts the lines before the start
+        assert_eq!(opened_at(content, ReadFrom::Position(StartPosition::End)), (14, 3));
+        assert_eq!(opened_at(content, ReadFrom::Position(StartPosition::Beginning)), (0, 0));
+        assert_eq!(opened_at(content, ReadFrom::Position(StartPosition::Offset(4))), (4, 1));
+        // an offset inside a line skips the rest of it
+        assert_eq!(opened_at(content, ReadFrom::Position(StartPosition::Offset(5))), (8, 2));
+        assert_eq!(opened_at(content, ReadFrom::Position(StartPosition::Offset(100))), (14, 3));
+        assert_eq!(opened_at(content, ReadFrom::Position(StartPosition::LastLines(2))), (4, 1));
+        assert_eq!(opened_at(content, ReadFrom::Position(StartPosition::LastLines(10))), (0, 0));
+        // a line without its newline yet is not counted
+        assert_eq!(opened_at("one\ntwo\nthr", ReadFrom::Position(StartPosition::LastLines(1))), (4, 1));
+    }
+
+    #[test]
+    fn last_lines_across_read_chunks() {
+        let content: String = (0..20_000).map(|i| format!("line-{i:05}\n")).collect();
+
+        let (position, line_number) = opened_at(&content, ReadFrom::Position(StartPosition::LastLines(3)));
+        assert_eq!((position, line_number), (content.len() as u64 - 3 * 11, 19_997));
+    }
+
+    #[test]
+    fn checkpoint_applies_only_to_the_same_file() {
+        let path = TempPath::new("resume.log");
+        fs::write(&path, "one\ntwo\nthree\n").unwrap();
+        let source = source_at(&path);
+        let inode = inode(&fs::metadata(&path).unwrap());
+
+        let resumed = Detector::open_reader(&source, ReadFrom::Resume(Checkpoint { inode, offset: 8, cursor: None })).unwrap();
+        assert_eq!((resumed.position, resumed.line_number), (8, 2));
+
+        let rotated = Detector::open_reader(&source, ReadFrom::Resume(Checkpoint { inode: inode + 1, offset: 8, cursor: None })).unwrap();
+        assert_eq!((rotated.position, rotated.line_number), (0, 0));
+
+        let truncated = Detector::open_reader(&source, ReadFrom::Resume(Checkpoint { inode, offset: 100, cursor: None })).unwrap();
+        assert_eq!((truncated.position, truncated.line_number), (0, 0));
+    }
+}
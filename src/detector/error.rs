@@ -3,13 +3,21 @@ use std::io;
 use std::io::ErrorKind;
 use tokio::sync::mpsc;
 
-use crate::log_event::LogEvent;
+use crate::{log_event::LogEvent, processor::ProcessorError};
 
 #[derive(Debug)]
 pub enum DetectError {
     Recoverable(io::Error),
     UnRecoverable(io::Error),
-    ChannelClosed(mpsc::error::SendError<LogEvent>),
+    ChannelClosed(Box<mpsc::error::SendError<LogEvent>>),
+    InvalidPipeline(ProcessorError),
+}
+
+impl DetectError {
+    // the log file was removed or not created yet
+    pub fn is_file_missing(&self) -> bool {
+        matches!(self, DetectError::UnRecoverable(e) if e.kind() == ErrorKind::NotFound)
+    }
 }
 
 impl From<io::Error> for DetectError {
@@ -21,18 +29,25 @@ impl From<io::Error> for DetectError {
     }
 }
 
-impl From<mpsc::error::SendError<LogEvent>> for DetectError {
-    fn from(value: mpsc::error::SendError<LogEvent>) -> Self {
+impl From<Box<mpsc::error::SendError<LogEvent>>> for DetectError {
+    fn from(value: Box<mpsc::error::SendError<LogEvent>>) -> Self {
         DetectError::ChannelClosed(value)
     }
 }
 
+impl From<ProcessorError> for DetectError {
+    fn from(value: ProcessorError) -> Self {
+        DetectError::InvalidPipeline(value)
+    }
+}
+
 impl Display for DetectError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             DetectError::Recoverable(e) => write!(f, "{e}"),
             DetectError::UnRecoverable(e) => write!(f, "{e}"),
             DetectError::ChannelClosed(e) => write!(f, "receiver channel closed: {e}"),
+            DetectError::InvalidPipeline(e) => write!(f, "invalid processors: {e}"),
         }
     }
 }
@@ -63,5 +78,6 @@ mod tests {
         let watch_error = DetectError::from(io_error);
 
         assert!(matches!(watch_error, DetectError::UnRecoverable(_)));
+        assert!(watch_error.is_file_missing());
     }
 }
@@ -0,0 +1,299 @@
+use chrono::DateTime;
+use serde_json::Value;
+use std::{
+    io::{self, BufReader},
+    process::{Child, ChildStdout, Command, Stdio},
+    sync::{atomic::Ordering, Arc},
+};
+use tracing::{info, trace, warn};
+
+use super::{Detect, DetectError, ReadFrom};
+use crate::{
+    checkpoint::Checkpoint,
+    config::{SourceConfig, StartPosition},
+    log_event::{LogEvent, SourceMeta},
+    metrics::{metrics, SourceMetrics},
+    parser::Fields,
+    processor::SourceStage,
+};
+
+mod export;
+use self::export::{Entry, ExportReader};
+
+const JOURNALCTL: &str = "journalctl";
+// reported as the path of journald sources reading the system journal
+const SYSTEM_JOURNAL: &str = "journal";
+
+// syslog priority names as journalctl accepts them, most severe first
+const PRIORITIES: [&str; 8] = ["emerg", "alert", "crit", "err", "warning", "notice", "info", "debug"];
+
+// a priority name or number, None when journalctl would not accept it
+pub fn journal_priority(value: &str) -> Option<usize> {
+    let value = value.trim().to_ascii_lowercase();
+
+    PRIORITIES
+        .iter()
+        .position(|name| *name == value)
+        .or_else(|| value.parse().ok().filter(|priority| *priority < PRIORITIES.len()))
+}
+
+// follows the journal through `journalctl --output=export --follow`, entries are checkpointed by
+// cursor and carry their realtime in microseconds as offset
+pub struct JournalDetector {
+    source: SourceConfig,
+    child: Child,
+    reader: ExportReader<BufReader<ChildStdout>>,
+    meta: Arc<SourceMeta>,
+    // after the last entry read, where this detector started until then
+    resume: ReadFrom,
+    stage: SourceStage,
+    metrics: Arc<SourceMetrics>,
+}
+
+impl JournalDetector {
+    pub fn build(source: SourceConfig, stage: SourceStage, read_from: ReadFrom) -> Result<Self, DetectError> {
+        let mut child = Command::new(JOURNALCTL)
+            .args(journalctl_args(&source, &read_from))
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            // not a missing log file, the supervisor backs off instead of waiting for `log_path`
+            .map_err(|e| DetectError::UnRecoverable(io::Error::other(format!("failed to run {JOURNALCTL}: {e}"))))?;
+        let stdout = child.stdout.take().expect("stdout of journalctl is piped");
+
+        let meta = Arc::new(SourceMeta {
+            path: source.journal_dir.clone().unwrap_or_else(|| SYSTEM_JOURNAL.to_string()),
+            inode: 0,
+            labels: source.labels.clone(),
+        });
+
+        Ok(Self {
+            metrics: metrics().source(&source.name),
+            source,
+            child,
+            reader: ExportReader::new(BufReader::with_capacity(64 * 1024, stdout)),
+            meta,
+            resume: read_from,
+            stage,
+        })
+    }
+
+    fn handle_entry(&mut self, entry: Entry) -> Result<(), DetectError> {
+        let event = to_event(&self.source, &self.meta, entry);
+        if let Some(cursor) = &event.cursor {
+            self.resume = ReadFrom::Resume(Checkpoint { inode: 0, offset: event.offset, cursor: Some(cursor.clone()) });
+        }
+
+        if event.log.is_empty() { return Ok(()); }
+        trace!("[{}] read journal entry", &self.source.name);
+
+        // periodic events of the pipeline go out with the next entry, journalctl blocks in between
+        self.stage.send(event)?;
+
+        Ok(())
+    }
+}
+
+impl Detect for JournalDetector {
+    fn detect(&mut self) -> Result<(), DetectError> {
+        info!("[{}] Started reading the journal", self.source.name);
+
+        loop {
+            match self.reader.next_entry() {
+                Ok(Some((entry, bytes))) => {
+                    self.metrics.lines_read.fetch_add(1, Ordering::Relaxed);
+                    self.metrics.bytes_read.fetch_add(bytes, Ordering::Relaxed);
+                    self.handle_entry(entry)?;
+                }
+                // journalctl only stops following when it fails, its message went to stderr
+                Ok(None) => {
+                    let status = self.child.wait()?;
+                    return Err(DetectError::UnRecoverable(io::Error::other(format!("{JOURNALCTL} exited with {status}"))));
+                }
+                Err(e) => match DetectError::from(e) {
+                    DetectError::Recoverable(e) => warn!("[{}] {e}", self.source.name),
+                    e => return Err(e),
+                },
+            }
+        }
+    }
+
+    fn resume_point(&self) -> ReadFrom {
+        self.resume.clone()
+    }
+}
+
+impl Drop for JournalDetector {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn journalctl_args(source: &SourceConfig, read_from: &ReadFrom) -> Vec<String> {
+    let mut args = vec!["--output=export".to_string(), "--follow".to_string()];
+
+    if let Some(journal_dir) = &source.journal_dir {
+        args.push(format!("--directory={journal_dir}"));
+    }
+    for unit in &source.units {
+        args.push(format!("--unit={unit}"));
+    }
+    if let Some(priority) = source.priority.as_deref().and_then(journal_priority) {
+        args.push(format!("--priority={priority}"));
+    }
+
+    let start = match read_from {
+        ReadFrom::Resume(Checkpoint { cursor: Some(cursor), .. }) => {
+            args.push(format!("--after-cursor={cursor}"));
+            StartPosition::Beginning
+        }
+        // a checkpoint without a cursor was left by a file source of the same name
+        ReadFrom::Resume(_) => source.start_position,
+        ReadFrom::Position(position) => *position,
+    };
+
+    args.push(match start {
+        StartPosition::Beginning => "--no-tail".to_string(),
+        StartPosition::LastLines(count) => format!("--lines={count}"),
+        // offsets are rejected by the config
+        StartPosition::End | StartPosition::Offset(_) => "--lines=0".to_string(),
+    });
+
+    args
+}
+
+// MESSAGE becomes the line, PRIORITY the level and every other field a lowercase field without
+// leading underscores, like `_SYSTEMD_UNIT` as `systemd_unit`
+fn to_event(source: &SourceConfig, meta: &Arc<SourceMeta>, entry: Entry) -> LogEvent {
+    let mut log = String::new();
+    let mut fields = Fields::new();
+    let mut cursor = None;
+    let mut realtime = None;
+    let mut source_realtime = None;
+
+    for (name, value) in entry {
+        match name.as_str() {
+            "MESSAGE" => log = value,
+            "__CURSOR" => cursor = Some(value),
+            "__REALTIME_TIMESTAMP" => realtime = value.parse::<u64>().ok(),
+            // when the logging process created the entry rather than when journald received it
+            "_SOURCE_REALTIME_TIMESTAMP" => source_realtime = value.parse::<i64>().ok(),
+            "PRIORITY" => {
+                if let Some(priority) = journal_priority(&value) {
+                    fields.insert(source.level_field.clone(), Value::String(PRIORITIES[priority].to_string()));
+                }
+            }
+            // the monotonic time and boot id of every entry
+            name if name.starts_with("__") => {}
+            name => {
+                fields.insert(name.trim_start_matches('_').to_ascii_lowercase(), Value::String(value));
+            }
+        }
+    }
+
+    let mut event = LogEvent::new(source.name.clone(), log, realtime.unwrap_or(0));
+    event.fields = fields;
+    event.meta = Some(meta.clone());
+    event.cursor = cursor;
+
+    let written = source_realtime.or(realtime.and_then(|realtime| i64::try_from(realtime).ok()));
+    if let Some(timestamp) = written.and_then(DateTime::from_timestamp_micros) {
+        event.timestamp = timestamp;
+    }
+
+    event
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn source(options: &str) -> SourceConfig {
+        toml::from_str(&format!(
+            r#"
+                name = "journal"
+                type = "journald"
+                {options}
+            "#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn journalctl_arguments() {
+        let source = source(
+            r#"
+                units = ["nginx.service", "app.service"]
+                priority = "warning"
+                journal_dir = "/var/log/journal"
+            "#,
+        );
+
+        assert_eq!(
+            journalctl_args(&source, &ReadFrom::Position(StartPosition::End)),
+            [
+                "--output=export",
+                "--follow",
+                "--directory=/var/log/journal",
+                "--unit=nginx.service",
+                "--unit=app.service",
+                "--priority=4",
+                "--lines=0",
+            ]
+        );
+
+        let resume = ReadFrom::Resume(Checkpoint { inode: 0, offset: 1, cursor: Some("s=abc;i=2".to_string()) });
+        assert_eq!(journalctl_args(&source, &resume)[6..], ["--after-cursor=s=abc;i=2", "--no-tail"]);
+
+        let file_checkpoint = ReadFrom::Resume(Checkpoint { inode: 7, offset: 1, cursor: None });
+        assert_eq!(journalctl_args(&source, &file_checkpoint)[6..], ["--lines=0"]);
+        assert_eq!(journalctl_args(&source, &ReadFrom::Position(StartPosition::LastLines(100)))[6..], ["--lines=100"]);
+    }
+
+    #[test]
+    fn maps_journal_fields() {
+        let source = source("");
+        let meta = Arc::new(SourceMeta { path: SYSTEM_JOURNAL.to_string(), inode: 0, labels: Default::default() });
+        let entry = [
+            ("__CURSOR", "s=abc;i=2"),
+            ("__REALTIME_TIMESTAMP", "1700000000000000"),
+            ("__MONOTONIC_TIMESTAMP", "12345"),
+            ("_SOURCE_REALTIME_TIMESTAMP", "1699999999000000"),
+            ("PRIORITY", "3"),
+            ("_SYSTEMD_UNIT", "nginx.service"),
+            ("SYSLOG_IDENTIFIER", "nginx"),
+            ("_PID", "42"),
+            ("MESSAGE", "upstream timed out"),
+        ]
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .to_vec();
+
+        let event = to_event(&source, &meta, entry);
+
+        assert_eq!(event.log, "upstream timed out");
+        assert_eq!(event.offset, 1700000000000000);
+        assert_eq!(event.cursor.as_deref(), Some("s=abc;i=2"));
+        assert_eq!(event.timestamp, DateTime::<Utc>::from_timestamp_micros(1699999999000000).unwrap());
+        assert_eq!(
+            Value::Object(event.fields),
+            serde_json::json!({
+                "level": "err",
+                "systemd_unit": "nginx.service",
+                "syslog_identifier": "nginx",
+                "pid": "42",
+            })
+        );
+    }
+
+    #[test]
+    fn priorities_by_name_or_number() {
+        assert_eq!(journal_priority("warning"), Some(4));
+        assert_eq!(journal_priority("ERR"), Some(3));
+        assert_eq!(journal_priority("7"), Some(7));
+        assert_eq!(journal_priority("8"), None);
+        assert_eq!(journal_priority("warn"), None);
+    }
+}
@@ -0,0 +1,115 @@
+use std::io::{self, BufRead, ErrorKind, Read};
+
+// fields of one journal entry in the order journalctl wrote them, binary values are decoded lossily
+pub type Entry = Vec<(String, String)>;
+
+// reads the journal export format: `NAME=value` lines, or the name, a little endian u64 length
+// and the raw value for values that contain newlines, entries separated by an empty line
+pub struct ExportReader<R> {
+    reader: R,
+    buf: Vec<u8>,
+}
+
+impl<R: BufRead> ExportReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buf: Vec::with_capacity(1024),
+        }
+    }
+
+    // the next entry and the bytes it took, None once the stream ended
+    pub fn next_entry(&mut self) -> io::Result<Option<(Entry, u64)>> {
+        let mut entry = Entry::new();
+        let mut bytes = 0;
+
+        loop {
+            self.buf.clear();
+            let read = self.reader.read_until(b'\n', &mut self.buf)?;
+            bytes += read as u64;
+
+            if read == 0 || self.buf == b"\n" {
+                if !entry.is_empty() {
+                    return Ok(Some((entry, bytes)));
+                }
+                if read == 0 {
+                    return Ok(None);
+                }
+                continue;
+            }
+
+            if self.buf.last() == Some(&b'\n') {
+                self.buf.pop();
+            }
+
+            match self.buf.iter().position(|&b| b == b'=') {
+                Some(separator) => {
+                    let name = String::from_utf8_lossy(&self.buf[..separator]).into_owned();
+                    let value = String::from_utf8_lossy(&self.buf[separator + 1..]).into_owned();
+                    entry.push((name, value));
+                }
+                None => {
+                    let name = String::from_utf8_lossy(&self.buf).into_owned();
+                    let (value, read) = self.binary_value()?;
+                    bytes += read;
+                    entry.push((name, value));
+                }
+            }
+        }
+    }
+
+    fn binary_value(&mut self) -> io::Result<(String, u64)> {
+        let mut len = [0; 8];
+        self.reader.read_exact(&mut len)?;
+        let len = u64::from_le_bytes(len);
+
+        let mut value = Vec::new();
+        (&mut self.reader).take(len).read_to_end(&mut value)?;
+        if (value.len() as u64) < len {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+
+        let mut newline = [0; 1];
+        self.reader.read_exact(&mut newline)?;
+        if newline != *b"\n" {
+            return Err(io::Error::new(ErrorKind::InvalidData, "binary journal field is not followed by a newline"));
+        }
+
+        Ok((String::from_utf8_lossy(&value).into_owned(), 8 + len + 1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_text_and_binary_fields() {
+        let mut export = b"__CURSOR=s=1;i=1\nMESSAGE=first\n\n__CURSOR=s=1;i=2\nMESSAGE\n".to_vec();
+        export.extend_from_slice(&11u64.to_le_bytes());
+        export.extend_from_slice(b"two\nlines=x\n_PID=42\n\n");
+
+        let mut reader = ExportReader::new(&export[..]);
+
+        let (first, bytes) = reader.next_entry().unwrap().unwrap();
+        assert_eq!(first, [("__CURSOR".to_string(), "s=1;i=1".to_string()), ("MESSAGE".to_string(), "first".to_string())]);
+        assert_eq!(bytes, 32);
+
+        let (second, bytes) = reader.next_entry().unwrap().unwrap();
+        assert_eq!(second[1], ("MESSAGE".to_string(), "two\nlines=x".to_string()));
+        assert_eq!(second[2], ("_PID".to_string(), "42".to_string()));
+        assert_eq!(bytes, export.len() as u64 - 32);
+
+        assert!(reader.next_entry().unwrap().is_none());
+    }
+
+    #[test]
+    fn truncated_binary_field_is_error() {
+        let mut export = b"MESSAGE\n".to_vec();
+        export.extend_from_slice(&100u64.to_le_bytes());
+        export.extend_from_slice(b"short");
+
+        let mut reader = ExportReader::new(&export[..]);
+        assert_eq!(reader.next_entry().unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,232 @@
+use std::{
+    path::Path,
+    sync::{atomic::Ordering, Arc},
+    thread,
+    time::{Duration, Instant},
+};
+use tracing::{error, info, warn};
+
+use super::{build_detector, Detect, DetectError, ReadFrom};
+use crate::{
+    config::{SourceConfig, StartPosition},
+    health::{DetectorState, SourceHealth},
+    metrics::SourceMetrics,
+    processor::SourceStage,
+};
+
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+// a detector that ran this long is healthy again, the next failure starts from the minimum backoff
+const HEALTHY_AFTER: Duration = Duration::from_secs(60);
+
+// keeps one source tailed, restarting its detector whenever it fails
+pub struct Supervisor {
+    source: SourceConfig,
+    stage: SourceStage,
+    health: Arc<SourceHealth>,
+    metrics: Arc<SourceMetrics>,
+    backoff: Duration,
+}
+
+impl Supervisor {
+    pub fn new(source: SourceConfig, stage: SourceStage, health: Arc<SourceHealth>, metrics: Arc<SourceMetrics>) -> Self {
+        Self {
+            source,
+            stage,
+            health,
+            metrics,
+            backoff: MIN_BACKOFF,
+        }
+    }
+
+    // returns once the event channel is closed, a file that was missing at startup is waited for first
+    pub fn run(mut self, initial: Result<Box<dyn Detect>, DetectError>) {
+        let _running = self.health.running();
+
+        let mut detector = match initial {
+            Ok(detector) => detector,
+            Err(e) => match self.recover(e, ReadFrom::Position(self.source.start_position)) {
+                Some(detector) => detector,
+                None => return self.stop(),
+            },
+        };
+
+        loop {
+            let started = Instant::now();
+            self.health.set_state(DetectorState::Running);
+
+            let error = match detector.detect() {
+                Ok(()) => return self.stop(),
+                Err(e) => e,
+            };
+
+            if started.elapsed() >= HEALTHY_AFTER {
+                self.backoff = MIN_BACKOFF;
+            }
+
+            detector = match self.recover(error, detector.resume_point()) {
+                Some(detector) => detector,
+                None => return self.stop(),
+            };
+
+            self.health.restarted();
+            self.metrics.restarts.fetch_add(1, Ordering::Relaxed);
+            info!("[{}] detector restarted", self.source.name);
+        }
+    }
+
+    // waits out the error and opens the source again, None when the agent shuts down
+    fn recover(&mut self, mut error: DetectError, resume: ReadFrom) -> Option<Box<dyn Detect>> {
+        loop {
+            // a file that appears is new, all of it is read
+            let read_from = match &error {
+                DetectError::ChannelClosed(_) => return None,
+                e if e.is_file_missing() => {
+                    if !self.wait_for_file(e) {
+                        return None;
+                    }
+                    ReadFrom::Position(StartPosition::Beginning)
+                }
+                e => {
+                    if !self.back_off(e) {
+                        return None;
+                    }
+                    resume.clone()
+                }
+            };
+
+            match build_detector(self.source.clone(), self.stage.clone(), read_from) {
+                Ok(detector) => return Some(detector),
+                Err(e) => error = e,
+            }
+        }
+    }
+
+    // returns false when the agent shuts down while waiting
+    fn wait_for_file(&self, error: &DetectError) -> bool {
+        warn!("[{}] {error}, waiting for {} to appear", self.source.name, self.source.log_path);
+        self.health.set_state(DetectorState::WaitingForFile);
+
+        let delay = Duration::from_millis(self.source.delay_ms);
+        while !Path::new(&self.source.log_path).exists() {
+            if self.stage.is_closed() {
+                return false;
+            }
+            thread::sleep(delay);
+        }
+
+        true
+    }
+
+    fn back_off(&mut self, error: &DetectError) -> bool {
+        error!("[{}] detector failed: {error}, restarting in {:?}", self.source.name, self.backoff);
+        self.health.set_state(DetectorState::Restarting { error: error.to_string() });
+
+        // sleep in small steps so shutdown is not held up by a long backoff
+        let deadline = Instant::now() + self.backoff;
+        while Instant::now() < deadline {
+            if self.stage.is_closed() {
+                return false;
+            }
+            thread::sleep(MIN_BACKOFF.min(deadline - Instant::now()));
+        }
+
+        self.backoff = (self.backoff * 2).min(MAX_BACKOFF);
+        true
+    }
+
+    fn stop(&self) {
+        info!("[{}] event channel closed, detector stopped", self.source.name);
+        self.health.set_state(DetectorState::Stopped);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{health::health, metrics::metrics, processor::Pipeline, test_util::TempPath};
+    use std::{fs, io::Write};
+    use tokio::sync::mpsc;
+
+    fn wait_until(mut condition: impl FnMut() -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !condition() {
+            assert!(Instant::now() < deadline, "timed out");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn restarts_when_the_file_reappears() {
+        let path = TempPath::new("supervisor.log");
+        fs::write(&path, "first\n").unwrap();
+
+        let source: SourceConfig = toml::from_str(&format!(
+            r#"
+                name = "supervisor-test"
+                log_path = "{}"
+                delay_ms = 10
+            "#,
+            path.display()
+        ))
+        .unwrap();
+
+        let (event_sender, mut event_receiver) = mpsc::channel(8);
+        let stage = SourceStage::new(Pipeline::build(&source).unwrap(), event_sender);
+        let source_health = health().source(&source.name);
+        let detector = build_detector(source.clone(), stage.clone(), ReadFrom::Position(StartPosition::End));
+        let supervisor = Supervisor::new(source.clone(), stage, source_health.clone(), metrics().source(&source.name));
+        thread::spawn(move || supervisor.run(detector));
+
+        fs::remove_file(&path).unwrap();
+        wait_until(|| source_health.state() == DetectorState::WaitingForFile);
+
+        fs::write(&path, "second\n").unwrap();
+        wait_until(|| source_health.restarts() == 1);
+
+        fs::OpenOptions::new().append(true).open(&path).unwrap().write_all(b"third\n").unwrap();
+        assert_eq!(event_receiver.blocking_recv().unwrap().log, "second");
+        assert_eq!(event_receiver.blocking_recv().unwrap().log, "third");
+        assert_eq!(metrics().source(&source.name).restarts.load(Ordering::Relaxed), 1);
+
+        drop(event_receiver);
+    }
+
+    #[test]
+    fn file_missing_at_startup_is_read_from_the_beginning() {
+        let path = TempPath::new("supervisor-missing.log");
+
+        let source: SourceConfig = toml::from_str(&format!(
+            r#"
+                name = "supervisor-missing-test"
+                log_path = "{}"
+                delay_ms = 10
+            "#,
+            path.display()
+        ))
+        .unwrap();
+
+        let (event_sender, mut event_receiver) = mpsc::channel(8);
+        let stage = SourceStage::new(Pipeline::build(&source).unwrap(), event_sender);
+        let source_health = health().source(&source.name);
+        let detector = build_detector(source.clone(), stage.clone(), ReadFrom::Position(StartPosition::End));
+        assert!(detector.as_ref().is_err_and(DetectError::is_file_missing));
+
+        let supervisor = Supervisor::new(source.clone(), stage, source_health.clone(), metrics().source(&source.name));
+        let handle = thread::spawn(move || supervisor.run(detector));
+        wait_until(|| source_health.state() == DetectorState::WaitingForFile);
+
+        fs::write(&path, "first\nsecond\n").unwrap();
+        let first = event_receiver.blocking_recv().unwrap();
+        let second = event_receiver.blocking_recv().unwrap();
+        assert_eq!((first.log.as_str(), first.line_number), ("first", 1));
+        assert_eq!((second.log.as_str(), second.line_number), ("second", 2));
+        assert_eq!(source_health.restarts(), 0);
+
+        // the next line fails to send and stops the detector
+        drop(event_receiver);
+        fs::OpenOptions::new().append(true).open(&path).unwrap().write_all(b"third\n").unwrap();
+        handle.join().unwrap();
+        assert_eq!(source_health.state(), DetectorState::Stopped);
+    }
+}
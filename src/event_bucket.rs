@@ -1,15 +1,20 @@
-use std::{collections::HashMap, time::Duration};
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
 use crate::{
     config::global_config,
-    log_event::LogEvent,
+    dedup::Deduplicator,
+    log_event::{LogEvent, SourceMeta},
+    metrics::{metrics, FlushTrigger},
     sender::payload::{Logs, Payload, Source},
 };
 use tokio::sync::mpsc::{Receiver, Sender};
 use tokio::task;
 
+// source name and inode, lines of a rotated file go into their own `Source`
+type BucketKey = (String, Option<u64>);
+
 pub struct EventBucket {
-    bucket: HashMap<String, Vec<Logs>>,
+    bucket: HashMap<BucketKey, (Option<Arc<SourceMeta>>, Vec<Logs>)>,
     max_batch_size: u8,
     total_size: u8,
 }
@@ -25,12 +30,13 @@ impl EventBucket {
 
     // insert bucket and check size if full of max_batch_size
     pub fn receive(&mut self, event: LogEvent) -> Option<()> {
-        let source_name = event.name.clone();
+        let key = (event.name.clone(), event.meta.as_ref().map(|meta| meta.inode));
+        let meta = event.meta.clone();
         let logs = Logs::from_event(event);
 
-        self.bucket.entry(source_name).or_default().push(logs);
+        self.bucket.entry(key).or_insert_with(|| (meta, Vec::new())).1.push(logs);
 
-        self.total_size += 1;
+        self.total_size = self.total_size.saturating_add(1);
 
         if self.total_size >= self.max_batch_size {
             return Some(());
@@ -43,20 +49,25 @@ impl EventBucket {
         self.bucket.is_empty()
     }
 
-    pub fn drain_to_payload(&mut self) -> Payload {
+    pub fn drain_to_payload(&mut self, trigger: FlushTrigger) -> Payload {
         self.total_size = 0;
+        metrics().batch_flushed(trigger);
 
         let log_datas = self
             .bucket
             .drain()
-            .map(|(name, logs)| Source::new(name, logs))
+            .map(|((name, _), (meta, logs))| Source::new(name, meta.as_deref(), logs))
             .collect();
 
         Payload::new(log_datas)
     }
 }
 
-pub fn spawn_event_aggregator(mut event_receiver: Receiver<LogEvent>, payload_sender: Sender<Payload>) -> task::JoinHandle<()> {
+pub fn spawn_event_aggregator(
+    mut event_receiver: Receiver<LogEvent>,
+    payload_sender: Sender<Payload>,
+    mut deduplicator: Deduplicator,
+) -> task::JoinHandle<()> {
     let mut event_bucket = EventBucket::new();
 
     let interval = Duration::from_secs(global_config().interval_secs);
@@ -66,8 +77,13 @@ pub fn spawn_event_aggregator(mut event_receiver: Receiver<LogEvent>, payload_se
         loop {
             tokio::select! {
                 _ = ticker.tick() => {
+                    // drained below anyway, the full signal does not matter
+                    for event in deduplicator.expire() {
+                        event_bucket.receive(event);
+                    }
+
                     if !event_bucket.is_empty() {
-                        let payload = event_bucket.drain_to_payload();
+                        let payload = event_bucket.drain_to_payload(FlushTrigger::Interval);
                         if payload_sender.send(payload).await.is_err() {
                             break;
                         }
@@ -76,16 +92,22 @@ pub fn spawn_event_aggregator(mut event_receiver: Receiver<LogEvent>, payload_se
                 receive = event_receiver.recv() => {
                     match receive {
                         Some(event) => {
+                            let Some(event) = deduplicator.push(event) else { continue };
+
                             if event_bucket.receive(event).is_some() {
-                                let payload = event_bucket.drain_to_payload();
+                                let payload = event_bucket.drain_to_payload(FlushTrigger::Size);
                                 if payload_sender.send(payload).await.is_err() {
                                     break;
                                 }
                             }
                         }
                         None => {
+                            for event in deduplicator.drain() {
+                                event_bucket.receive(event);
+                            }
+
                             if !event_bucket.is_empty() {
-                                let payload = event_bucket.drain_to_payload();
+                                let payload = event_bucket.drain_to_payload(FlushTrigger::Shutdown);
                                 if payload_sender.send(payload).await.is_err() {
                                     break;
                                 }
@@ -0,0 +1,240 @@
+use regex::RegexSet;
+use serde_json::Value;
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use tracing::{debug, trace};
+
+use crate::{
+    config::SourceConfig,
+    log_event::LogEvent,
+    metrics::{metrics, SourceMetrics},
+    parser::Fields,
+    processor::{Outcome, Processor},
+};
+
+pub mod error;
+pub use error::FilterError;
+
+const SUMMARY_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum Level {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Fatal,
+}
+
+impl Level {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "trace" | "trc" => Some(Level::Trace),
+            "debug" | "dbg" => Some(Level::Debug),
+            "info" | "information" | "notice" => Some(Level::Info),
+            "warn" | "warning" | "wrn" => Some(Level::Warn),
+            "error" | "err" => Some(Level::Error),
+            "fatal" | "critical" | "crit" | "panic" | "alert" | "emerg" => Some(Level::Fatal),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct FilterCounters {
+    pub not_included: u64,
+    pub excluded: u64,
+    pub below_level: u64,
+}
+
+impl FilterCounters {
+    pub fn total(&self) -> u64 {
+        self.not_included + self.excluded + self.below_level
+    }
+}
+
+// drops lines of one source before they are sent to the aggregator
+pub struct LineFilter {
+    source_name: String,
+    include: Option<RegexSet>,
+    exclude: Option<RegexSet>,
+    min_level: Option<Level>,
+    level_field: String,
+    counters: FilterCounters,
+    reported: FilterCounters,
+    last_summary: Instant,
+    metrics: Arc<SourceMetrics>,
+}
+
+impl LineFilter {
+    // include / exclude, checked before parsing so dropped lines cost as little as possible
+    pub fn lines(source: &SourceConfig) -> Result<Option<Self>, FilterError> {
+        let include = (!source.include.is_empty()).then(|| RegexSet::new(&source.include)).transpose()?;
+        let exclude = (!source.exclude.is_empty()).then(|| RegexSet::new(&source.exclude)).transpose()?;
+
+        if include.is_none() && exclude.is_none() {
+            return Ok(None);
+        }
+
+        Ok(Some(Self::new(source, include, exclude, None)))
+    }
+
+    // min_level, checked after parsing so the level field exists
+    pub fn level(source: &SourceConfig) -> Result<Option<Self>, FilterError> {
+        let min_level = source
+            .min_level
+            .as_deref()
+            .map(|level| Level::parse(level).ok_or_else(|| FilterError::UnknownLevel(level.to_string())))
+            .transpose()?;
+
+        Ok(min_level.map(|min_level| Self::new(source, None, None, Some(min_level))))
+    }
+
+    fn new(source: &SourceConfig, include: Option<RegexSet>, exclude: Option<RegexSet>, min_level: Option<Level>) -> Self {
+        Self {
+            source_name: source.name.clone(),
+            include,
+            exclude,
+            min_level,
+            level_field: source.level_field.clone(),
+            counters: FilterCounters::default(),
+            reported: FilterCounters::default(),
+            last_summary: Instant::now(),
+            metrics: metrics().source(&source.name),
+        }
+    }
+
+    // include / exclude on the raw line
+    pub fn accept_line(&mut self, line: &str) -> bool {
+        if self.include.as_ref().is_some_and(|include| !include.is_match(line)) {
+            self.counters.not_included += 1;
+            return self.drop_line("not included", |m| &m.not_included);
+        }
+
+        if self.exclude.as_ref().is_some_and(|exclude| exclude.is_match(line)) {
+            self.counters.excluded += 1;
+            return self.drop_line("excluded", |m| &m.excluded);
+        }
+
+        true
+    }
+
+    // level threshold on the parsed level field, lines without a known level pass
+    pub fn accept_fields(&mut self, fields: &Fields) -> bool {
+        let Some(min_level) = self.min_level else { return true };
+
+        let level = match fields.get(&self.level_field) {
+            Some(Value::String(level)) => Level::parse(level),
+            _ => None,
+        };
+
+        if level.is_some_and(|level| level < min_level) {
+            self.counters.below_level += 1;
+            return self.drop_line("below level", |m| &m.below_level);
+        }
+
+        true
+    }
+
+    fn drop_line(&mut self, reason: &str, counter: fn(&SourceMetrics) -> &AtomicU64) -> bool {
+        trace!("[{}] dropped line ({reason})", self.source_name);
+        counter(&self.metrics).fetch_add(1, Ordering::Relaxed);
+
+        if self.last_summary.elapsed() >= SUMMARY_INTERVAL {
+            let dropped = self.counters.total() - self.reported.total();
+            debug!(
+                "[{}] filter dropped {dropped} lines in last {}s (total not included: {}, excluded: {}, below level: {})",
+                self.source_name,
+                self.last_summary.elapsed().as_secs(),
+                self.counters.not_included,
+                self.counters.excluded,
+                self.counters.below_level,
+            );
+
+            self.reported = self.counters;
+            self.last_summary = Instant::now();
+        }
+
+        false
+    }
+}
+
+// each filter only holds the checks of its own step, the others always pass
+impl Processor for LineFilter {
+    fn process(&mut self, event: LogEvent) -> Outcome {
+        if self.accept_line(&event.log) && self.accept_fields(&event.fields) {
+            Outcome::Keep(event)
+        } else {
+            Outcome::Drop
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn source(include: &[&str], exclude: &[&str], min_level: Option<&str>) -> SourceConfig {
+        let mut source: SourceConfig = toml::from_str(
+            r#"
+                name = "app1"
+                log_path = "app1.log"
+            "#,
+        )
+        .unwrap();
+        source.include = include.iter().map(|s| s.to_string()).collect();
+        source.exclude = exclude.iter().map(|s| s.to_string()).collect();
+        source.min_level = min_level.map(str::to_string);
+        source
+    }
+
+    fn fields(value: Value) -> Fields {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn no_rules_has_no_filter() {
+        assert!(LineFilter::lines(&source(&[], &[], Some("info"))).unwrap().is_none());
+        assert!(LineFilter::level(&source(&["ERROR"], &[], None)).unwrap().is_none());
+    }
+
+    #[test]
+    fn include_and_exclude() {
+        let mut filter = LineFilter::lines(&source(&["ERROR", "WARN"], &["healthz"], None)).unwrap().unwrap();
+
+        assert!(filter.accept_line("ERROR db down"));
+        assert!(!filter.accept_line("INFO started"));
+        assert!(!filter.accept_line("WARN GET /healthz slow"));
+
+        assert_eq!(
+            filter.counters,
+            FilterCounters { not_included: 1, excluded: 1, below_level: 0 }
+        );
+    }
+
+    #[test]
+    fn level_threshold() {
+        let mut filter = LineFilter::level(&source(&[], &[], Some("info"))).unwrap().unwrap();
+
+        assert!(!filter.accept_fields(&fields(json!({"level": "DEBUG"}))));
+        assert!(filter.accept_fields(&fields(json!({"level": "info"}))));
+        assert!(filter.accept_fields(&fields(json!({"level": "Warning"}))));
+        assert!(filter.accept_fields(&fields(json!({"msg": "no level"}))));
+        assert!(filter.accept_fields(&fields(json!({"level": "verbose"}))));
+
+        assert_eq!(filter.counters.below_level, 1);
+    }
+
+    #[test]
+    fn invalid_rules_are_error() {
+        assert!(matches!(LineFilter::lines(&source(&["("], &[], None)), Err(FilterError::InvalidPattern(_))));
+        assert!(matches!(LineFilter::level(&source(&[], &[], Some("loud"))), Err(FilterError::UnknownLevel(_))));
+    }
+}
@@ -0,0 +1,22 @@
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug)]
+pub enum FilterError {
+    InvalidPattern(regex::Error),
+    UnknownLevel(String),
+}
+
+impl From<regex::Error> for FilterError {
+    fn from(value: regex::Error) -> Self {
+        FilterError::InvalidPattern(value)
+    }
+}
+
+impl Display for FilterError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FilterError::InvalidPattern(e) => write!(f, "Invalid filter pattern: {e}"),
+            FilterError::UnknownLevel(level) => write!(f, "Unknown level '{level}'"),
+        }
+    }
+}
@@ -0,0 +1,302 @@
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, LazyLock, Mutex,
+    },
+};
+
+use crate::metrics::metrics;
+
+// not ready once a spool is this full, the oldest payloads are about to be dropped
+const SPOOL_READY_RATIO: f64 = 0.8;
+
+static HEALTH: LazyLock<Health> = LazyLock::new(Health::default);
+
+pub fn health() -> &'static Health {
+    &HEALTH
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum DetectorState {
+    Starting,
+    Running,
+    // the log file does not exist, the supervisor polls until it appears
+    WaitingForFile,
+    // the detector failed and is restarted after a backoff
+    Restarting { error: String },
+    // the event channel closed on shutdown
+    Stopped,
+    Failed { error: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reachability {
+    // nothing was sent yet
+    Unknown,
+    Reachable,
+    Unreachable,
+}
+
+pub struct SourceHealth {
+    state: Mutex<DetectorState>,
+    restarts: AtomicU64,
+}
+
+pub struct OutputHealth {
+    reachability: Mutex<Reachability>,
+    circuit_open: AtomicBool,
+    spool_capacity: Mutex<Option<usize>>,
+}
+
+// what the status server reports on /healthz and /readyz
+#[derive(Default)]
+pub struct Health {
+    config_loaded: AtomicBool,
+    sources: Mutex<BTreeMap<String, Arc<SourceHealth>>>,
+    outputs: Mutex<BTreeMap<String, Arc<OutputHealth>>>,
+}
+
+pub struct Report {
+    pub ok: bool,
+    pub body: Value,
+}
+
+impl SourceHealth {
+    pub fn state(&self) -> DetectorState {
+        lock(&self.state).clone()
+    }
+
+    pub fn set_state(&self, state: DetectorState) {
+        *lock(&self.state) = state;
+    }
+
+    pub fn restarted(&self) {
+        self.restarts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn restarts(&self) -> u64 {
+        self.restarts.load(Ordering::Relaxed)
+    }
+
+    // marks the detector running until the guard is dropped, so a thread that dies
+    // without reporting an error (a panic) still shows up as failed
+    pub fn running(self: &Arc<Self>) -> RunningGuard {
+        self.set_state(DetectorState::Running);
+        RunningGuard(self.clone())
+    }
+}
+
+pub struct RunningGuard(Arc<SourceHealth>);
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        let mut state = lock(&self.0.state);
+        if !matches!(*state, DetectorState::Stopped | DetectorState::Failed { .. }) {
+            *state = DetectorState::Failed { error: "detector thread exited".to_string() };
+        }
+    }
+}
+
+impl OutputHealth {
+    pub fn set_reachability(&self, reachability: Reachability) {
+        *lock(&self.reachability) = reachability;
+    }
+
+    pub fn set_circuit_open(&self, open: bool) {
+        self.circuit_open.store(open, Ordering::Relaxed);
+    }
+
+    pub fn set_spool_capacity(&self, capacity: usize) {
+        *lock(&self.spool_capacity) = Some(capacity);
+    }
+}
+
+impl Health {
+    pub fn set_config_loaded(&self) {
+        self.config_loaded.store(true, Ordering::Relaxed);
+    }
+
+    pub fn source(&self, name: &str) -> Arc<SourceHealth> {
+        lock(&self.sources)
+            .entry(name.to_string())
+            .or_insert_with(|| {
+                Arc::new(SourceHealth {
+                    state: Mutex::new(DetectorState::Starting),
+                    restarts: AtomicU64::new(0),
+                })
+            })
+            .clone()
+    }
+
+    pub fn output(&self, name: &str) -> Arc<OutputHealth> {
+        lock(&self.outputs)
+            .entry(name.to_string())
+            .or_insert_with(|| {
+                Arc::new(OutputHealth {
+                    reachability: Mutex::new(Reachability::Unknown),
+                    circuit_open: AtomicBool::new(false),
+                    spool_capacity: Mutex::new(None),
+                })
+            })
+            .clone()
+    }
+
+    // alive while no detector has failed
+    pub fn liveness(&self) -> Report {
+        let (sources, sources_ok) = self.sources();
+
+        Report {
+            ok: sources_ok,
+            body: json!({ "status": status(sources_ok), "sources": sources }),
+        }
+    }
+
+    // ready when the config is loaded and every output can take payloads
+    pub fn readiness(&self) -> Report {
+        let config_loaded = self.config_loaded.load(Ordering::Relaxed);
+        let (sources, _) = self.sources();
+
+        let mut outputs_ok = true;
+        let mut outputs = serde_json::Map::new();
+        for (name, output) in lock(&self.outputs).iter() {
+            let reachability = *lock(&output.reachability);
+            let circuit_open = output.circuit_open.load(Ordering::Relaxed);
+            let spool_size = metrics().output(name).spool_size.load(Ordering::Relaxed);
+            let spool_capacity = *lock(&output.spool_capacity);
+
+            let spool_ok = spool_capacity.is_none_or(|capacity| (spool_size as f64) < capacity as f64 * SPOOL_READY_RATIO);
+            let ok = reachability != Reachability::Unreachable && !circuit_open && spool_ok;
+            outputs_ok &= ok;
+
+            outputs.insert(
+                name.clone(),
+                json!({
+                    "status": status(ok),
+                    "reachability": reachability,
+                    "circuit_open": circuit_open,
+                    "spool_size": spool_size,
+                    "spool_capacity": spool_capacity,
+                }),
+            );
+        }
+
+        let ok = config_loaded && outputs_ok;
+        Report {
+            ok,
+            body: json!({
+                "status": status(ok),
+                "config_loaded": config_loaded,
+                "outputs": outputs,
+                "sources": sources,
+            }),
+        }
+    }
+
+    fn sources(&self) -> (Value, bool) {
+        let mut ok = true;
+        let mut sources = serde_json::Map::new();
+
+        for (name, source) in lock(&self.sources).iter() {
+            let state = source.state();
+            ok &= !matches!(state, DetectorState::Failed { .. });
+
+            let mut detail = json!(state);
+            detail["restarts"] = json!(source.restarts());
+            sources.insert(name.clone(), detail);
+        }
+
+        (Value::Object(sources), ok)
+    }
+}
+
+fn status(ok: bool) -> &'static str {
+    if ok { "ok" } else { "unavailable" }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_detector_is_not_alive() {
+        let health = Health::default();
+        let app1 = health.source("app1");
+        let app2 = health.source("app2");
+
+        let _guard = app1.running();
+        drop(app2.running());
+        app1.restarted();
+
+        let report = health.liveness();
+        assert!(!report.ok);
+        assert_eq!(report.body["sources"]["app1"]["state"], "running");
+        assert_eq!(report.body["sources"]["app1"]["restarts"], 1);
+        assert_eq!(report.body["sources"]["app2"]["state"], "failed");
+        assert_eq!(report.body["sources"]["app2"]["error"], "detector thread exited");
+    }
+
+    #[test]
+    fn reported_error_is_kept_after_exit() {
+        let health = Health::default();
+        let source = health.source("app1");
+
+        let guard = source.running();
+        source.set_state(DetectorState::Failed { error: "permission denied".to_string() });
+        drop(guard);
+
+        assert_eq!(health.liveness().body["sources"]["app1"]["error"], "permission denied");
+    }
+
+    #[test]
+    fn stopped_and_restarting_detectors_are_alive() {
+        let health = Health::default();
+        let app1 = health.source("app1");
+        let app2 = health.source("app2");
+
+        drop({
+            let guard = app1.running();
+            app1.set_state(DetectorState::Stopped);
+            guard
+        });
+        let _guard = app2.running();
+        app2.set_state(DetectorState::Restarting { error: "permission denied".to_string() });
+
+        let report = health.liveness();
+        assert!(report.ok);
+        assert_eq!(report.body["sources"]["app1"]["state"], "stopped");
+        assert_eq!(report.body["sources"]["app2"]["state"], "restarting");
+    }
+
+    #[test]
+    fn readiness_checks_outputs() {
+        let health = Health::default();
+        assert!(!health.readiness().ok);
+
+        health.set_config_loaded();
+        let output = health.output("health-test");
+        output.set_spool_capacity(10);
+        assert!(health.readiness().ok);
+
+        output.set_circuit_open(true);
+        let report = health.readiness();
+        assert!(!report.ok);
+        assert_eq!(report.body["outputs"]["health-test"]["circuit_open"], true);
+
+        output.set_circuit_open(false);
+        output.set_reachability(Reachability::Unreachable);
+        assert!(!health.readiness().ok);
+
+        output.set_reachability(Reachability::Reachable);
+        metrics().output("health-test").spool_size.store(8, Ordering::Relaxed);
+        assert!(!health.readiness().ok);
+    }
+}
@@ -1,17 +1,55 @@
 use chrono::{DateTime, Utc};
+use std::sync::Arc;
 
+use crate::{config::Labels, parser::Fields};
+
+// where lines are read from, shared by every event of the same opened file
+#[derive(Debug, PartialEq)]
+pub struct SourceMeta {
+    // absolute path
+    pub path: String,
+    pub inode: u64,
+    pub labels: Labels,
+}
+
+#[derive(Clone)]
 pub struct LogEvent {
     pub name: String,
     pub log: String,
+    // when the log was written, taken from the line when configured
     pub timestamp: DateTime<Utc>,
+    // when the detector read the line
+    pub observed_timestamp: DateTime<Utc>,
+    // byte offset in the file right after this line, the realtime in microseconds for journal entries
+    pub offset: u64,
+    pub fields: Fields,
+    // the line could not be parsed with the configured format
+    pub parse_error: bool,
+    pub meta: Option<Arc<SourceMeta>>,
+    // 1-based line number in the file
+    pub line_number: u64,
+    // journal cursor of the entry, journald sources only
+    pub cursor: Option<String>,
+    // identical lines collapsed into this event, 1 for a single line
+    pub repeat_count: u64,
 }
 
 impl LogEvent {
-    pub fn new(name: String, log: String) -> Self {
+    pub fn new(name: String, log: String, offset: u64) -> Self {
+        let now = Utc::now();
+
         Self {
             name,
             log,
-            timestamp: Utc::now(),
+            timestamp: now,
+            observed_timestamp: now,
+            offset,
+            fields: Fields::new(),
+            parse_error: false,
+            meta: None,
+            line_number: 0,
+            cursor: None,
+            repeat_count: 1,
         }
     }
 }
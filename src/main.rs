@@ -1,16 +1,33 @@
 use crate::{
+    checkpoint::CheckpointStore,
+    cli::Command,
     config::{global_config, load_config},
     log_event::LogEvent,
-    sender::payload::Payload,
+    sender::{delivery::DeliveryReport, payload::Payload},
 };
+use std::sync::Arc;
 use tokio::sync::mpsc;
 use tracing::error;
 
+mod backfill;
+mod checkpoint;
+mod cli;
 mod config;
+mod dedup;
 mod event_bucket;
+mod filter;
+mod health;
 mod log_event;
+mod metrics;
+mod parser;
+mod processor;
+mod redact;
+mod throttle;
 mod sender;
+mod server;
 mod detector;
+#[cfg(test)]
+mod test_util;
 
 #[tokio::main]
 async fn main() {
@@ -20,24 +37,71 @@ async fn main() {
         .with_thread_ids(true)
         .init();
 
+    let command = match cli::parse(std::env::args().skip(1)) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("{e}\n\n{}", cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+
     // load configuration
-    let sources = match load_config() {
-        Ok(sources) => sources,
+    let (sources, outputs) = match load_config() {
+        Ok(config) => config,
         Err(e) => {
             error!("{e}");
+            if matches!(command, Command::Backfill(_)) {
+                std::process::exit(1);
+            }
             return;
         }
     };
 
+    if let Command::Backfill(args) = command {
+        if let Err(e) = backfill::run(args, sources, outputs).await {
+            error!("{e}");
+            // scripts running a backfill need to see that it did not complete
+            std::process::exit(1);
+        }
+        return;
+    }
+
     // create mpsc
     let channel_bound =  global_config().channel_bound;
 
-    // detector -> aggregator
+    // detector (running the processors of its source) -> aggregator
     let (event_sender, event_receiver) = mpsc::channel::<LogEvent>(channel_bound);
     // aggregator -> sender
     let (payload_sender, payload_receiver) = mpsc::channel::<Payload>(channel_bound);
+    // sender -> delivery reports
+    let (report_sender, report_receiver) = mpsc::unbounded_channel::<DeliveryReport>();
 
-    let detector_handles = match detector::spawn_detectors(event_sender, sources) {
+    metrics::metrics().register_channel("event", &event_sender);
+    metrics::metrics().register_channel("payload", &payload_sender);
+
+    if let Some(addr) = global_config().metrics_addr {
+        match tokio::net::TcpListener::bind(addr).await {
+            Ok(listener) => {
+                server::spawn_status_server(listener);
+            }
+            Err(e) => {
+                error!("failed to bind status listener on {addr}: {e}");
+                return;
+            }
+        }
+    }
+
+    let deduplicator = dedup::Deduplicator::build(&sources);
+    let checkpoints = match global_config().checkpoint_path.as_deref().map(CheckpointStore::load).transpose() {
+        Ok(checkpoints) => checkpoints.map(Arc::new),
+        Err(e) => {
+            error!("{e}");
+            return;
+        }
+    };
+    health::health().set_config_loaded();
+
+    let detector_handles = match detector::spawn_detectors(event_sender, sources, checkpoints.as_deref()) {
         Ok(hs) => hs,
         Err(e) => {
             error!("{e}");
@@ -45,9 +109,11 @@ async fn main() {
         }
     };
 
-    let aggregator_handle = event_bucket::spawn_event_aggregator(event_receiver, payload_sender);
+    let aggregator_handle = event_bucket::spawn_event_aggregator(event_receiver, payload_sender, deduplicator);
+
+    let report_handle = sender::delivery::spawn_delivery_logger(report_receiver, checkpoints);
 
-    let sender_handle = match sender::spawn_sender(payload_receiver) {
+    let sender_handle = match sender::spawn_sender(payload_receiver, outputs, report_sender) {
         Ok(h) => h,
         Err(e) => {
             error!("{e}");
@@ -61,6 +127,7 @@ async fn main() {
 
     let _ = aggregator_handle.await;
     let _ = sender_handle.await;
+    let _ = report_handle.await;
 
     error!("Detectors All Closed Process Exit..");
 }
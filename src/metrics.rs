@@ -0,0 +1,279 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, LazyLock, Mutex,
+    },
+};
+use tokio::sync::mpsc;
+
+mod histogram;
+use self::histogram::Histogram;
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::default);
+
+pub fn metrics() -> &'static Metrics {
+    &METRICS
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum FlushTrigger {
+    Size,
+    Interval,
+    Shutdown,
+}
+
+// depth of a channel, None once it is closed
+type ChannelDepth = Box<dyn Fn() -> Option<usize> + Send + Sync>;
+
+type SourceCounter = fn(&SourceMetrics) -> &AtomicU64;
+
+// registry of everything the agent counts, rendered in the Prometheus text format
+#[derive(Default)]
+pub struct Metrics {
+    sources: Mutex<BTreeMap<String, Arc<SourceMetrics>>>,
+    outputs: Mutex<BTreeMap<String, Arc<OutputMetrics>>>,
+    channels: Mutex<Vec<(&'static str, ChannelDepth)>>,
+    batches_by_size: AtomicU64,
+    batches_by_interval: AtomicU64,
+    batches_on_shutdown: AtomicU64,
+}
+
+#[derive(Default)]
+pub struct SourceMetrics {
+    pub lines_read: AtomicU64,
+    pub bytes_read: AtomicU64,
+    pub rotations: AtomicU64,
+    pub restarts: AtomicU64,
+    // lines dropped by the filters, by reason
+    pub not_included: AtomicU64,
+    pub excluded: AtomicU64,
+    pub below_level: AtomicU64,
+    // lines dropped by rate_limit and sampling
+    pub rate_limited: AtomicU64,
+    pub sampled_out: AtomicU64,
+    // processor -> events it failed on and passed on unchanged
+    processor_failures: Mutex<BTreeMap<&'static str, u64>>,
+}
+
+impl SourceMetrics {
+    pub fn processor_failed(&self, processor: &'static str) {
+        *lock(&self.processor_failures).entry(processor).or_default() += 1;
+    }
+}
+
+#[derive(Default)]
+pub struct OutputMetrics {
+    pub send_duration: Histogram,
+    // attempt number -> retries
+    retries: Mutex<BTreeMap<u8, u64>>,
+    pub failures: AtomicU64,
+    pub spool_size: AtomicU64,
+}
+
+impl OutputMetrics {
+    pub fn retried(&self, attempt: u8) {
+        *self.retries.lock().unwrap_or_else(|e| e.into_inner()).entry(attempt).or_default() += 1;
+    }
+
+    pub fn failed(&self) {
+        self.failures.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl Metrics {
+    // components keep the returned handle so counting does not touch the registry lock
+    pub fn source(&self, name: &str) -> Arc<SourceMetrics> {
+        lock(&self.sources).entry(name.to_string()).or_default().clone()
+    }
+
+    pub fn output(&self, name: &str) -> Arc<OutputMetrics> {
+        lock(&self.outputs).entry(name.to_string()).or_default().clone()
+    }
+
+    // only a weak sender is kept so the registry never holds a channel open
+    pub fn register_channel<T: Send + 'static>(&self, name: &'static str, sender: &mpsc::Sender<T>) {
+        let weak = sender.downgrade();
+        let depth: ChannelDepth = Box::new(move || weak.upgrade().map(|sender| sender.max_capacity() - sender.capacity()));
+
+        lock(&self.channels).push((name, depth));
+    }
+
+    pub fn batch_flushed(&self, trigger: FlushTrigger) {
+        let counter = match trigger {
+            FlushTrigger::Size => &self.batches_by_size,
+            FlushTrigger::Interval => &self.batches_by_interval,
+            FlushTrigger::Shutdown => &self.batches_on_shutdown,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        let sources = lock(&self.sources);
+        let source_counters: [(&str, &str, SourceCounter); 4] = [
+            ("log_agent_lines_read_total", "Lines read from the source file", |m| &m.lines_read),
+            ("log_agent_bytes_read_total", "Bytes read from the source file", |m| &m.bytes_read),
+            ("log_agent_rotations_total", "Rotations detected", |m| &m.rotations),
+            ("log_agent_detector_restarts_total", "Detector restarts after a failure", |m| &m.restarts),
+        ];
+        for (name, help, counter) in source_counters {
+            header(&mut out, name, help, "counter");
+            for (source, metrics) in sources.iter() {
+                let _ = writeln!(out, "{name}{{source=\"{}\"}} {}", escape(source), counter(metrics).load(Ordering::Relaxed));
+            }
+        }
+
+        header(&mut out, "log_agent_lines_filtered_total", "Lines dropped by the filters of the source", "counter");
+        for (source, metrics) in sources.iter() {
+            for (reason, counter) in [
+                ("not_included", &metrics.not_included),
+                ("excluded", &metrics.excluded),
+                ("below_level", &metrics.below_level),
+            ] {
+                let _ = writeln!(
+                    out,
+                    "log_agent_lines_filtered_total{{source=\"{}\",reason=\"{reason}\"}} {}",
+                    escape(source),
+                    counter.load(Ordering::Relaxed)
+                );
+            }
+        }
+
+        header(&mut out, "log_agent_lines_throttled_total", "Lines dropped by rate limiting or sampling", "counter");
+        for (source, metrics) in sources.iter() {
+            for (reason, counter) in [("rate_limited", &metrics.rate_limited), ("sampled_out", &metrics.sampled_out)] {
+                let _ = writeln!(
+                    out,
+                    "log_agent_lines_throttled_total{{source=\"{}\",reason=\"{reason}\"}} {}",
+                    escape(source),
+                    counter.load(Ordering::Relaxed)
+                );
+            }
+        }
+
+        header(&mut out, "log_agent_processor_failures_total", "Events a processor failed on and passed on unchanged", "counter");
+        for (source, metrics) in sources.iter() {
+            for (processor, count) in lock(&metrics.processor_failures).iter() {
+                let _ = writeln!(
+                    out,
+                    "log_agent_processor_failures_total{{source=\"{}\",processor=\"{processor}\"}} {count}",
+                    escape(source)
+                );
+            }
+        }
+        drop(sources);
+
+        header(&mut out, "log_agent_channel_depth", "Messages waiting in an internal channel", "gauge");
+        for (channel, depth) in lock(&self.channels).iter() {
+            if let Some(depth) = depth() {
+                let _ = writeln!(out, "log_agent_channel_depth{{channel=\"{channel}\"}} {depth}");
+            }
+        }
+
+        header(&mut out, "log_agent_batches_flushed_total", "Batches flushed by the aggregator", "counter");
+        for (trigger, counter) in [
+            ("size", &self.batches_by_size),
+            ("interval", &self.batches_by_interval),
+            ("shutdown", &self.batches_on_shutdown),
+        ] {
+            let _ = writeln!(out, "log_agent_batches_flushed_total{{trigger=\"{trigger}\"}} {}", counter.load(Ordering::Relaxed));
+        }
+
+        let outputs = lock(&self.outputs);
+        header(&mut out, "log_agent_send_duration_seconds", "Duration of one send attempt", "histogram");
+        for (output, metrics) in outputs.iter() {
+            metrics.send_duration.render(&mut out, "log_agent_send_duration_seconds", &format!("output=\"{}\"", escape(output)));
+        }
+
+        header(&mut out, "log_agent_retries_total", "Retries by attempt number", "counter");
+        for (output, metrics) in outputs.iter() {
+            for (attempt, count) in lock(&metrics.retries).iter() {
+                let _ = writeln!(out, "log_agent_retries_total{{output=\"{}\",attempt=\"{attempt}\"}} {count}", escape(output));
+            }
+        }
+
+        header(&mut out, "log_agent_send_failures_total", "Payloads that failed permanently", "counter");
+        for (output, metrics) in outputs.iter() {
+            let _ = writeln!(out, "log_agent_send_failures_total{{output=\"{}\"}} {}", escape(output), metrics.failures.load(Ordering::Relaxed));
+        }
+
+        header(&mut out, "log_agent_spool_size", "Payloads spooled while the circuit is open", "gauge");
+        for (output, metrics) in outputs.iter() {
+            let _ = writeln!(out, "log_agent_spool_size{{output=\"{}\"}} {}", escape(output), metrics.spool_size.load(Ordering::Relaxed));
+        }
+
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, help: &str, kind: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn renders_registered_metrics() {
+        let metrics = Metrics::default();
+
+        let source = metrics.source("app\"1");
+        source.lines_read.fetch_add(3, Ordering::Relaxed);
+        source.bytes_read.fetch_add(42, Ordering::Relaxed);
+        source.excluded.fetch_add(2, Ordering::Relaxed);
+        source.sampled_out.fetch_add(4, Ordering::Relaxed);
+        source.processor_failed("script");
+
+        let output = metrics.output("default");
+        output.send_duration.observe(Duration::from_millis(20));
+        output.retried(2);
+        output.retried(2);
+        output.failed();
+        output.spool_size.store(5, Ordering::Relaxed);
+
+        metrics.batch_flushed(FlushTrigger::Size);
+
+        let (sender, _receiver) = mpsc::channel::<u8>(4);
+        sender.try_send(1).unwrap();
+        metrics.register_channel("event", &sender);
+
+        let out = metrics.render();
+
+        assert!(out.contains("# TYPE log_agent_lines_read_total counter\n"));
+        assert!(out.contains("log_agent_lines_read_total{source=\"app\\\"1\"} 3\n"));
+        assert!(out.contains("log_agent_bytes_read_total{source=\"app\\\"1\"} 42\n"));
+        assert!(out.contains("log_agent_lines_filtered_total{source=\"app\\\"1\",reason=\"excluded\"} 2\n"));
+        assert!(out.contains("log_agent_lines_throttled_total{source=\"app\\\"1\",reason=\"sampled_out\"} 4\n"));
+        assert!(out.contains("log_agent_processor_failures_total{source=\"app\\\"1\",processor=\"script\"} 1\n"));
+        assert!(out.contains("log_agent_channel_depth{channel=\"event\"} 1\n"));
+        assert!(out.contains("log_agent_batches_flushed_total{trigger=\"size\"} 1\n"));
+        assert!(out.contains("log_agent_send_duration_seconds_count{output=\"default\"} 1\n"));
+        assert!(out.contains("log_agent_retries_total{output=\"default\",attempt=\"2\"} 2\n"));
+        assert!(out.contains("log_agent_send_failures_total{output=\"default\"} 1\n"));
+        assert!(out.contains("log_agent_spool_size{output=\"default\"} 5\n"));
+    }
+
+    #[test]
+    fn closed_channel_is_not_rendered() {
+        let metrics = Metrics::default();
+        let (sender, _receiver) = mpsc::channel::<u8>(4);
+        metrics.register_channel("payload", &sender);
+        drop(sender);
+
+        assert!(!metrics.render().contains("channel=\"payload\""));
+    }
+}
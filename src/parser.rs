@@ -0,0 +1,206 @@
+use serde_json::{Map, Value};
+use tracing::trace;
+
+use crate::{
+    config::{LogFormat, SourceConfig},
+    log_event::LogEvent,
+    processor::{Outcome, Processor},
+};
+
+pub mod error;
+pub use error::ParseError;
+
+mod json;
+use self::json::JsonParser;
+
+mod logfmt;
+use self::logfmt::LogfmtParser;
+
+mod patterns;
+mod timestamp;
+use self::timestamp::TimestampExtractor;
+
+mod regex_parser;
+use self::regex_parser::RegexParser;
+
+pub type Fields = Map<String, Value>;
+
+pub trait Parser: Send {
+    fn parse(&self, line: &str) -> Result<Fields, ParseError>;
+}
+
+// parses lines of one source into fields and takes the log timestamp from them
+pub struct LineParser {
+    source_name: String,
+    parser: Option<Box<dyn Parser>>,
+    timestamp: Option<TimestampExtractor>,
+}
+
+impl LineParser {
+    pub fn build(source: &SourceConfig) -> Result<Option<Self>, ParseError> {
+        let parser: Option<Box<dyn Parser>> = match source.format {
+            LogFormat::Raw => None,
+            LogFormat::Json => Some(Box::new(JsonParser)),
+            LogFormat::Regex => Some(Box::new(RegexParser::build(
+                source.pattern.as_deref(),
+                source.pattern_name.as_deref(),
+            )?)),
+            LogFormat::Logfmt => Some(Box::new(LogfmtParser)),
+        };
+
+        let timestamp = source.timestamp.as_ref().map(TimestampExtractor::build).transpose()?;
+        if parser.is_none() && timestamp.is_none() {
+            return Ok(None);
+        }
+
+        Ok(Some(Self {
+            source_name: source.name.clone(),
+            parser,
+            timestamp,
+        }))
+    }
+
+    // on failure the raw line is kept and the event is flagged,
+    // without a timestamp in the line the detection time is kept
+    pub fn apply(&self, event: &mut LogEvent) {
+        if let Some(parser) = &self.parser {
+            match parser.parse(&event.log) {
+                Ok(fields) => event.fields = fields,
+                Err(e) => {
+                    trace!("[{}] failed to parse line: {e}", self.source_name);
+                    event.parse_error = true;
+                }
+            }
+        }
+
+        if let Some(extractor) = &self.timestamp {
+            match extractor.extract(&event.log, &event.fields) {
+                Some(timestamp) => event.timestamp = timestamp,
+                None => trace!("[{}] no timestamp in line, using observed time", self.source_name),
+            }
+        }
+    }
+}
+
+impl Processor for LineParser {
+    fn process(&mut self, mut event: LogEvent) -> Outcome {
+        self.apply(&mut event);
+        Outcome::Keep(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::source_config::TimestampConfig;
+
+    fn source(format: LogFormat, timestamp_field: Option<&str>) -> SourceConfig {
+        let mut source: SourceConfig = toml::from_str(
+            r#"
+                name = "app1"
+                log_path = "app1.log"
+            "#,
+        )
+        .unwrap();
+        source.format = format;
+        source.timestamp = timestamp_field.map(|field| TimestampConfig {
+            field: Some(field.to_string()),
+            pattern: None,
+            format: None,
+            timezone: None,
+        });
+        source
+    }
+
+    fn event(line: &str) -> LogEvent {
+        LogEvent::new("app1".to_string(), line.to_string(), 0)
+    }
+
+    #[test]
+    fn raw_format_has_no_parser() {
+        assert!(LineParser::build(&source(LogFormat::Raw, None)).unwrap().is_none());
+    }
+
+    #[test]
+    fn json_line_becomes_fields() {
+        let parser = LineParser::build(&source(LogFormat::Json, None)).unwrap().unwrap();
+        let mut event = event(r#"{"level":"warn","msg":"disk"}"#);
+
+        parser.apply(&mut event);
+
+        assert_eq!(event.fields["level"], "warn");
+        assert!(!event.parse_error);
+    }
+
+    #[test]
+    fn timestamp_field_is_lifted() {
+        let parser = LineParser::build(&source(LogFormat::Json, Some("time"))).unwrap().unwrap();
+        let mut event = event(r#"{"time":"2024-01-15T10:30:00.123+09:00","msg":"x"}"#);
+
+        parser.apply(&mut event);
+
+        assert_eq!(event.timestamp.to_rfc3339(), "2024-01-15T01:30:00.123+00:00");
+    }
+
+    #[test]
+    fn raw_format_with_timestamp_pattern() {
+        let mut source = source(LogFormat::Raw, None);
+        source.timestamp = Some(TimestampConfig {
+            field: None,
+            pattern: Some(r"^(\S+ \S+)".to_string()),
+            format: Some("%Y-%m-%d %H:%M:%S".to_string()),
+            timezone: Some("+09:00".to_string()),
+        });
+        let parser = LineParser::build(&source).unwrap().unwrap();
+
+        let mut event = event("2024-01-15 10:30:00 INFO started");
+        let observed = event.observed_timestamp;
+        parser.apply(&mut event);
+
+        assert_eq!(event.timestamp.to_rfc3339(), "2024-01-15T01:30:00+00:00");
+        assert_eq!(event.observed_timestamp, observed);
+        assert!(event.fields.is_empty());
+    }
+
+    #[test]
+    fn missing_timestamp_falls_back_to_observed() {
+        let parser = LineParser::build(&source(LogFormat::Json, Some("time"))).unwrap().unwrap();
+        let mut event = event(r#"{"time":"not a time"}"#);
+
+        parser.apply(&mut event);
+
+        assert_eq!(event.timestamp, event.observed_timestamp);
+        assert!(!event.parse_error);
+    }
+
+    #[test]
+    fn invalid_line_keeps_raw_and_flags_error() {
+        let parser = LineParser::build(&source(LogFormat::Json, Some("time"))).unwrap().unwrap();
+        let mut event = event("not json");
+
+        parser.apply(&mut event);
+
+        assert_eq!(event.log, "not json");
+        assert!(event.fields.is_empty());
+        assert!(event.parse_error);
+    }
+
+    #[test]
+    fn regex_format_uses_pattern() {
+        let mut source = source(LogFormat::Regex, None);
+        source.pattern_name = Some("spring_boot".to_string());
+        let parser = LineParser::build(&source).unwrap().unwrap();
+
+        let mut event = event("2024-01-15 10:30:00.123 ERROR 1 --- [main] c.e.App : boom");
+        parser.apply(&mut event);
+
+        assert_eq!(event.fields["level"], "ERROR");
+        assert_eq!(event.fields["message"], "boom");
+    }
+
+    #[test]
+    fn regex_format_without_pattern_is_error() {
+        let result = LineParser::build(&source(LogFormat::Regex, None));
+        assert!(matches!(result, Err(ParseError::MissingPattern)));
+    }
+}
@@ -0,0 +1,44 @@
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug)]
+pub enum ParseError {
+    InvalidJson(serde_json::Error),
+    NotAnObject,
+    NoMatch,
+    InvalidPattern(regex::Error),
+    NoNamedCapture,
+    UnknownPattern(String),
+    MissingPattern,
+    InvalidLogfmt(String),
+    MissingTimestampSource,
+    InvalidTimezone(String),
+}
+
+impl From<serde_json::Error> for ParseError {
+    fn from(value: serde_json::Error) -> Self {
+        ParseError::InvalidJson(value)
+    }
+}
+
+impl From<regex::Error> for ParseError {
+    fn from(value: regex::Error) -> Self {
+        ParseError::InvalidPattern(value)
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::InvalidJson(e) => write!(f, "Invalid JSON: {e}"),
+            ParseError::NotAnObject => write!(f, "JSON line is not an object"),
+            ParseError::NoMatch => write!(f, "Line does not match the pattern"),
+            ParseError::InvalidPattern(e) => write!(f, "Invalid pattern: {e}"),
+            ParseError::NoNamedCapture => write!(f, "Pattern has no named capture group"),
+            ParseError::UnknownPattern(name) => write!(f, "Unknown built-in pattern '{name}'"),
+            ParseError::MissingPattern => write!(f, "Regex format needs exactly one of pattern or pattern_name"),
+            ParseError::InvalidLogfmt(reason) => write!(f, "Invalid logfmt: {reason}"),
+            ParseError::MissingTimestampSource => write!(f, "Timestamp needs exactly one of field or pattern"),
+            ParseError::InvalidTimezone(timezone) => write!(f, "Invalid timezone '{timezone}'"),
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use serde_json::Value;
+
+use super::{Fields, ParseError, Parser};
+
+pub struct JsonParser;
+
+impl Parser for JsonParser {
+    fn parse(&self, line: &str) -> Result<Fields, ParseError> {
+        match serde_json::from_str(line)? {
+            Value::Object(fields) => Ok(fields),
+            _ => Err(ParseError::NotAnObject),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parse_object() {
+        let fields = JsonParser.parse(r#"{"level":"info","msg":"started","took":3}"#).expect("parse");
+
+        assert_eq!(fields["level"], json!("info"));
+        assert_eq!(fields["took"], json!(3));
+    }
+
+    #[test]
+    fn invalid_json_is_error() {
+        let result = JsonParser.parse("level=info msg=started");
+        assert!(matches!(result, Err(ParseError::InvalidJson(_))));
+    }
+
+    #[test]
+    fn non_object_is_error() {
+        let result = JsonParser.parse(r#"["a", "b"]"#);
+        assert!(matches!(result, Err(ParseError::NotAnObject)));
+    }
+}
@@ -0,0 +1,140 @@
+use serde_json::Value;
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+use super::{Fields, ParseError, Parser};
+
+// key=value pairs separated by spaces, values may be double quoted with backslash escapes.
+// a key without '=' is a flag and becomes true.
+pub struct LogfmtParser;
+
+impl Parser for LogfmtParser {
+    fn parse(&self, line: &str) -> Result<Fields, ParseError> {
+        let mut fields = Fields::new();
+        let mut chars = line.char_indices().peekable();
+
+        loop {
+            while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+            let Some(&(start, _)) = chars.peek() else { break };
+
+            let key = take_until(line, &mut chars, start, |c| c == '=' || c.is_whitespace());
+            if key.is_empty() {
+                return Err(ParseError::InvalidLogfmt(format!("missing key at {start}")));
+            }
+            if key.contains('"') {
+                return Err(ParseError::InvalidLogfmt(format!("quote in key '{key}'")));
+            }
+
+            let value = if chars.next_if(|&(_, c)| c == '=').is_some() {
+                match chars.peek() {
+                    Some(&(_, '"')) => Value::String(take_quoted(&mut chars)?),
+                    Some(&(start, _)) => Value::String(take_until(line, &mut chars, start, char::is_whitespace).to_string()),
+                    None => Value::String(String::new()),
+                }
+            } else {
+                Value::Bool(true)
+            };
+
+            fields.insert(key.to_string(), value);
+        }
+
+        Ok(fields)
+    }
+}
+
+fn take_until<'a>(
+    line: &'a str,
+    chars: &mut Peekable<CharIndices<'a>>,
+    start: usize,
+    stop: impl Fn(char) -> bool,
+) -> &'a str {
+    let mut end = line.len();
+    while let Some(&(i, c)) = chars.peek() {
+        if stop(c) {
+            end = i;
+            break;
+        }
+        chars.next();
+    }
+
+    &line[start..end]
+}
+
+fn take_quoted(chars: &mut Peekable<CharIndices<'_>>) -> Result<String, ParseError> {
+    let (start, _) = chars.next().expect("opening quote");
+    let mut value = String::new();
+
+    while let Some((_, c)) = chars.next() {
+        match c {
+            '"' => {
+                if chars.peek().is_some_and(|&(_, c)| !c.is_whitespace()) {
+                    return Err(ParseError::InvalidLogfmt(format!("unexpected character after quoted value at {start}")));
+                }
+                return Ok(value);
+            }
+            '\\' => match chars.next() {
+                Some((_, 'n')) => value.push('\n'),
+                Some((_, 't')) => value.push('\t'),
+                Some((_, 'r')) => value.push('\r'),
+                Some((_, escaped)) => value.push(escaped),
+                None => break,
+            },
+            _ => value.push(c),
+        }
+    }
+
+    Err(ParseError::InvalidLogfmt(format!("unterminated quote at {start}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parse_pairs() {
+        let fields = LogfmtParser.parse(r#"level=info msg="x y" dur=3ms"#).unwrap();
+
+        assert_eq!(fields["level"], json!("info"));
+        assert_eq!(fields["msg"], json!("x y"));
+        assert_eq!(fields["dur"], json!("3ms"));
+    }
+
+    #[test]
+    fn escaped_quotes_and_newlines() {
+        let fields = LogfmtParser.parse(r#"msg="said \"hi\"\nbye" path="C:\\tmp""#).unwrap();
+
+        assert_eq!(fields["msg"], json!("said \"hi\"\nbye"));
+        assert_eq!(fields["path"], json!("C:\\tmp"));
+    }
+
+    #[test]
+    fn bare_key_and_empty_value() {
+        let fields = LogfmtParser.parse("debug err= code=500").unwrap();
+
+        assert_eq!(fields["debug"], json!(true));
+        assert_eq!(fields["err"], json!(""));
+        assert_eq!(fields["code"], json!("500"));
+    }
+
+    #[test]
+    fn unicode_and_extra_spaces() {
+        let fields = LogfmtParser.parse("  user=김철수   msg=\"안녕 하세요\"  ").unwrap();
+
+        assert_eq!(fields["user"], json!("김철수"));
+        assert_eq!(fields["msg"], json!("안녕 하세요"));
+    }
+
+    #[test]
+    fn value_may_contain_equals() {
+        let fields = LogfmtParser.parse("query=a=b&c=d").unwrap();
+        assert_eq!(fields["query"], json!("a=b&c=d"));
+    }
+
+    #[test]
+    fn invalid_lines_are_errors() {
+        assert!(matches!(LogfmtParser.parse(r#"msg="unterminated"#), Err(ParseError::InvalidLogfmt(_))));
+        assert!(matches!(LogfmtParser.parse("=value"), Err(ParseError::InvalidLogfmt(_))));
+        assert!(matches!(LogfmtParser.parse(r#"msg="a"b"#), Err(ParseError::InvalidLogfmt(_))));
+    }
+}
@@ -0,0 +1,125 @@
+// built-in regex patterns for common log formats, referenced by `pattern_name`
+
+// 127.0.0.1 - frank [10/Oct/2000:13:55:36 -0700] "GET /a.gif HTTP/1.0" 200 2326 "http://ref/" "Mozilla/4.08"
+const COMBINED: &str = r#"^(?P<remote_addr>\S+) (?P<ident>\S+) (?P<remote_user>\S+) \[(?P<time_local>[^\]]+)\] "(?P<method>[A-Z]+) (?P<path>\S+)(?: (?P<protocol>[^"]+))?" (?P<status>\d{3}) (?P<body_bytes_sent>\d+|-)(?: "(?P<referer>[^"]*)" "(?P<user_agent>[^"]*)")?"#;
+
+// <34>Oct 11 22:14:15 mymachine su[230]: 'su root' failed for lonvick on /dev/pts/8
+const SYSLOG: &str = r"^(?:<(?P<priority>\d{1,3})>)?(?P<timestamp>[A-Z][a-z]{2} [ \d]\d \d{2}:\d{2}:\d{2}) (?P<hostname>\S+) (?P<app_name>[^\s:\[]+)(?:\[(?P<pid>\d+)\])?: (?P<message>.*)$";
+
+// time=2024-01-15T10:30:00Z level=info msg="request done", common keys only, see format = "logfmt" for every key
+const LOGFMT: &str = r#"^(?:.*?\b(?:time|ts)=(?P<time>"[^"]*"|\S+))?(?:.*?\blevel=(?P<level>"[^"]*"|\S+))?(?:.*?\bmsg=(?P<message>"(?:[^"\\]|\\.)*"|\S+))?"#;
+
+// 2024-01-15 10:30:00.123  INFO 12345 --- [main] com.example.App : Started App
+// 2024-01-15T10:30:00.123+09:00  INFO 12345 --- [app] [main] com.example.App : Started App
+const SPRING_BOOT: &str = r"^(?P<timestamp>\d{4}-\d{2}-\d{2}[ T]\d{2}:\d{2}:\d{2}(?:\.\d+)?(?:Z|[+-]\d{2}:?\d{2})?)\s+(?P<level>[A-Z]+)\s+(?P<pid>\d+)\s+---\s+(?:\[(?P<application>[^\]]*)\]\s+)?\[\s*(?P<thread>[^\]]+)\]\s+(?P<logger>\S+)\s*:\s(?P<message>.*)$";
+
+pub struct Builtin {
+    pub pattern: &'static str,
+    // values may be captured with their double quotes, which are removed and unescaped
+    pub quoted_values: bool,
+}
+
+pub fn builtin(name: &str) -> Option<Builtin> {
+    let (pattern, quoted_values) = match name {
+        "nginx_combined" | "apache_combined" => (COMBINED, false),
+        "syslog" => (SYSLOG, false),
+        "logfmt" => (LOGFMT, true),
+        "spring_boot" => (SPRING_BOOT, false),
+        _ => return None,
+    };
+
+    Some(Builtin { pattern, quoted_values })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{regex_parser::RegexParser, Parser};
+    use regex::Regex;
+
+    fn captures(name: &str, line: &str) -> Vec<(String, String)> {
+        let regex = Regex::new(builtin(name).unwrap().pattern).unwrap();
+        let captures = regex.captures(line).expect("line matches");
+
+        regex
+            .capture_names()
+            .flatten()
+            .filter_map(|group| captures.name(group).map(|m| (group.to_string(), m.as_str().to_string())))
+            .collect()
+    }
+
+    fn get<'a>(fields: &'a [(String, String)], key: &str) -> Option<&'a str> {
+        fields.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    #[test]
+    fn combined_access_log() {
+        let fields = captures(
+            "nginx_combined",
+            r#"127.0.0.1 - frank [10/Oct/2000:13:55:36 -0700] "GET /apache_pb.gif HTTP/1.0" 200 2326 "http://www.example.com/start.html" "Mozilla/4.08""#,
+        );
+
+        assert_eq!(get(&fields, "remote_addr"), Some("127.0.0.1"));
+        assert_eq!(get(&fields, "remote_user"), Some("frank"));
+        assert_eq!(get(&fields, "method"), Some("GET"));
+        assert_eq!(get(&fields, "path"), Some("/apache_pb.gif"));
+        assert_eq!(get(&fields, "status"), Some("200"));
+        assert_eq!(get(&fields, "user_agent"), Some("Mozilla/4.08"));
+    }
+
+    #[test]
+    fn syslog_line() {
+        let fields = captures("syslog", "<34>Oct 11 22:14:15 mymachine su[230]: 'su root' failed");
+
+        assert_eq!(get(&fields, "priority"), Some("34"));
+        assert_eq!(get(&fields, "hostname"), Some("mymachine"));
+        assert_eq!(get(&fields, "app_name"), Some("su"));
+        assert_eq!(get(&fields, "pid"), Some("230"));
+        assert_eq!(get(&fields, "message"), Some("'su root' failed"));
+    }
+
+    #[test]
+    fn logfmt_common_keys() {
+        let parser = RegexParser::build(None, Some("logfmt")).unwrap();
+        let fields = parser
+            .parse(r#"time="2024-01-15 10:30:00" level=info msg="request \"done\"" dur=3ms"#)
+            .unwrap();
+
+        assert_eq!(fields["time"], "2024-01-15 10:30:00");
+        assert_eq!(fields["level"], "info");
+        assert_eq!(fields["message"], r#"request "done""#);
+
+        // without any of the keys the line is not logfmt
+        assert!(parser.parse("plain text line").is_err());
+    }
+
+    #[test]
+    fn spring_boot_default() {
+        let fields = captures(
+            "spring_boot",
+            "2024-01-15 10:30:00.123  INFO 12345 --- [           main] com.example.App                 : Started App in 2.1 seconds",
+        );
+
+        assert_eq!(get(&fields, "level"), Some("INFO"));
+        assert_eq!(get(&fields, "thread"), Some("main"));
+        assert_eq!(get(&fields, "logger"), Some("com.example.App"));
+        assert_eq!(get(&fields, "message"), Some("Started App in 2.1 seconds"));
+    }
+
+    #[test]
+    fn spring_boot_3_with_application_name() {
+        let fields = captures(
+            "spring_boot",
+            "2024-01-15T10:30:00.123+09:00  WARN 12345 --- [orders] [nio-8080-exec-1] o.s.web.Servlet : Slow request",
+        );
+
+        assert_eq!(get(&fields, "timestamp"), Some("2024-01-15T10:30:00.123+09:00"));
+        assert_eq!(get(&fields, "application"), Some("orders"));
+        assert_eq!(get(&fields, "thread"), Some("nio-8080-exec-1"));
+    }
+
+    #[test]
+    fn unknown_name_is_none() {
+        assert!(builtin("unknown").is_none());
+    }
+}
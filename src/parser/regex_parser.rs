@@ -0,0 +1,135 @@
+use regex::Regex;
+use serde_json::Value;
+
+use super::{patterns, Fields, ParseError, Parser};
+
+pub struct RegexParser {
+    regex: Regex,
+    quoted_values: bool,
+}
+
+impl RegexParser {
+    // pattern is a custom regex, pattern_name one of the built-in patterns
+    pub fn build(pattern: Option<&str>, pattern_name: Option<&str>) -> Result<Self, ParseError> {
+        let (pattern, quoted_values) = match (pattern, pattern_name) {
+            (Some(pattern), None) => (pattern, false),
+            (None, Some(name)) => {
+                let builtin = patterns::builtin(name).ok_or_else(|| ParseError::UnknownPattern(name.to_string()))?;
+                (builtin.pattern, builtin.quoted_values)
+            }
+            _ => return Err(ParseError::MissingPattern),
+        };
+
+        let regex = Regex::new(pattern)?;
+        if regex.capture_names().flatten().next().is_none() {
+            return Err(ParseError::NoNamedCapture);
+        }
+
+        Ok(Self { regex, quoted_values })
+    }
+}
+
+impl Parser for RegexParser {
+    // a match where every group is optional and none captured does not count as parsed
+    fn parse(&self, line: &str) -> Result<Fields, ParseError> {
+        let captures = self.regex.captures(line).ok_or(ParseError::NoMatch)?;
+
+        let fields: Fields = self
+            .regex
+            .capture_names()
+            .flatten()
+            .filter_map(|name| {
+                captures.name(name).map(|m| {
+                    let value = if self.quoted_values { unquote(m.as_str()) } else { m.as_str().to_string() };
+                    (name.to_string(), Value::String(value))
+                })
+            })
+            .collect();
+
+        if fields.is_empty() {
+            return Err(ParseError::NoMatch);
+        }
+
+        Ok(fields)
+    }
+}
+
+// "request \"done\"" becomes request "done", unquoted values are kept as they are
+fn unquote(value: &str) -> String {
+    let Some(inner) = value.strip_prefix('"').and_then(|value| value.strip_suffix('"')) else {
+        return value.to_string();
+    };
+
+    let mut unescaped = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('r') => unescaped.push('\r'),
+            Some(escaped) => unescaped.push(escaped),
+            None => unescaped.push('\\'),
+        }
+    }
+
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn named_captures_become_fields() {
+        let parser = RegexParser::build(
+            Some(r"^(?P<level>[A-Z]+) \[(?P<thread>[^\]]+)\] (?P<logger>\S+) - (?P<message>.*)$"),
+            None,
+        )
+        .unwrap();
+
+        let fields = parser.parse("ERROR [worker-1] com.example.Job - failed to run").unwrap();
+
+        assert_eq!(fields["level"], "ERROR");
+        assert_eq!(fields["thread"], "worker-1");
+        assert_eq!(fields["logger"], "com.example.Job");
+        assert_eq!(fields["message"], "failed to run");
+    }
+
+    #[test]
+    fn optional_group_without_match_is_skipped() {
+        let parser = RegexParser::build(Some(r"^(?P<level>[A-Z]+)(?: (?P<code>\d+))? (?P<message>.*)$"), None).unwrap();
+
+        let fields = parser.parse("INFO started").unwrap();
+
+        assert!(!fields.contains_key("code"));
+        assert_eq!(fields["message"], "started");
+    }
+
+    #[test]
+    fn unmatched_line_is_error() {
+        let parser = RegexParser::build(Some(r"^(?P<level>[A-Z]+) "), None).unwrap();
+        assert!(matches!(parser.parse("lowercase line"), Err(ParseError::NoMatch)));
+    }
+
+    #[test]
+    fn builtin_pattern_by_name() {
+        let parser = RegexParser::build(None, Some("syslog")).unwrap();
+        let fields = parser.parse("Oct 11 22:14:15 host sshd[1]: accepted").unwrap();
+
+        assert_eq!(fields["app_name"], "sshd");
+    }
+
+    #[test]
+    fn invalid_patterns_are_rejected() {
+        assert!(matches!(RegexParser::build(Some("(?P<a>"), None), Err(ParseError::InvalidPattern(_))));
+        assert!(matches!(RegexParser::build(Some(r"^\d+$"), None), Err(ParseError::NoNamedCapture)));
+        assert!(matches!(RegexParser::build(None, Some("unknown")), Err(ParseError::UnknownPattern(_))));
+        assert!(matches!(RegexParser::build(None, None), Err(ParseError::MissingPattern)));
+        assert!(matches!(RegexParser::build(Some("(?P<a>.)"), Some("syslog")), Err(ParseError::MissingPattern)));
+    }
+}
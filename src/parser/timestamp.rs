@@ -0,0 +1,249 @@
+use chrono::{DateTime, Datelike, FixedOffset, Local, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use regex::Regex;
+use serde_json::Value;
+
+use super::{Fields, ParseError};
+use crate::config::source_config::TimestampConfig;
+
+// tried in order when no format is configured
+const AUTO_FORMATS: [&str; 3] = ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f", "%d/%b/%Y:%H:%M:%S"];
+
+#[derive(Debug, Clone, Copy)]
+pub enum Timezone {
+    Utc,
+    Local,
+    Fixed(FixedOffset),
+    Named(Tz),
+}
+
+impl Timezone {
+    // "UTC", "Local", "+09:00" or an IANA name such as "Asia/Seoul"
+    pub fn parse(value: &str) -> Result<Self, ParseError> {
+        match value {
+            "UTC" | "utc" | "Z" => return Ok(Timezone::Utc),
+            "Local" | "local" => return Ok(Timezone::Local),
+            _ => {}
+        }
+
+        if let Ok(offset) = value.parse::<FixedOffset>() {
+            return Ok(Timezone::Fixed(offset));
+        }
+
+        value
+            .parse::<Tz>()
+            .map(Timezone::Named)
+            .map_err(|_| ParseError::InvalidTimezone(value.to_string()))
+    }
+
+    fn to_utc(self, naive: &NaiveDateTime) -> Option<DateTime<Utc>> {
+        match self {
+            Timezone::Utc => Some(naive.and_utc()),
+            Timezone::Local => Local.from_local_datetime(naive).earliest().map(|t| t.to_utc()),
+            Timezone::Fixed(offset) => offset.from_local_datetime(naive).earliest().map(|t| t.to_utc()),
+            Timezone::Named(tz) => tz.from_local_datetime(naive).earliest().map(|t| t.to_utc()),
+        }
+    }
+}
+
+// takes the time a log was written from a parsed field or from the raw line
+pub struct TimestampExtractor {
+    field: Option<String>,
+    pattern: Option<Regex>,
+    format: Option<String>,
+    timezone: Timezone,
+}
+
+impl TimestampExtractor {
+    pub fn build(config: &TimestampConfig) -> Result<Self, ParseError> {
+        if config.field.is_none() == config.pattern.is_none() {
+            return Err(ParseError::MissingTimestampSource);
+        }
+
+        let pattern = config.pattern.as_deref().map(Regex::new).transpose()?;
+        let timezone = match &config.timezone {
+            Some(timezone) => Timezone::parse(timezone)?,
+            None => Timezone::Utc,
+        };
+
+        Ok(Self {
+            field: config.field.clone(),
+            pattern,
+            format: config.format.clone(),
+            timezone,
+        })
+    }
+
+    pub fn extract(&self, line: &str, fields: &Fields) -> Option<DateTime<Utc>> {
+        if let Some(field) = &self.field {
+            return match fields.get(field)? {
+                Value::String(value) => self.parse(value),
+                Value::Number(value) => from_epoch(value.as_i64()?),
+                _ => None,
+            };
+        }
+
+        // the group named "timestamp", otherwise the first group, otherwise the whole match
+        let captures = self.pattern.as_ref()?.captures(line)?;
+        let value = captures
+            .name("timestamp")
+            .or_else(|| captures.get(1))
+            .or_else(|| captures.get(0))?;
+
+        self.parse(value.as_str())
+    }
+
+    fn parse(&self, value: &str) -> Option<DateTime<Utc>> {
+        let value = value.trim();
+
+        match &self.format {
+            Some(format) => self.parse_with_format(value, format),
+            None => DateTime::parse_from_rfc3339(value)
+                .map(|t| t.to_utc())
+                .ok()
+                .or_else(|| AUTO_FORMATS.iter().find_map(|format| self.parse_with_format(value, format)))
+                .or_else(|| from_epoch(value.parse().ok()?)),
+        }
+    }
+
+    fn parse_with_format(&self, value: &str, format: &str) -> Option<DateTime<Utc>> {
+        // the value carries its own offset (%z), timezone does not apply
+        if let Ok(timestamp) = DateTime::parse_from_str(value, format) {
+            return Some(timestamp.to_utc());
+        }
+
+        if let Ok(naive) = NaiveDateTime::parse_from_str(value, format) {
+            return self.timezone.to_utc(&naive);
+        }
+
+        // formats without a year such as syslog "Oct 11 22:14:15" are this year, or last year around new year
+        let now = Utc::now();
+        let naive = NaiveDateTime::parse_from_str(&format!("{} {value}", now.year()), &format!("%Y {format}")).ok()?;
+        let timestamp = self.timezone.to_utc(&naive)?;
+
+        if timestamp > now + chrono::Duration::days(1) {
+            let last_year = naive.with_year(now.year() - 1)?;
+            return self.timezone.to_utc(&last_year);
+        }
+
+        Some(timestamp)
+    }
+}
+
+// unix epoch in seconds or milliseconds
+fn from_epoch(epoch: i64) -> Option<DateTime<Utc>> {
+    if epoch.abs() < 100_000_000_000 {
+        DateTime::from_timestamp(epoch, 0)
+    } else {
+        DateTime::from_timestamp_millis(epoch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn extractor(field: Option<&str>, pattern: Option<&str>, format: Option<&str>, timezone: Option<&str>) -> TimestampExtractor {
+        TimestampExtractor::build(&TimestampConfig {
+            field: field.map(str::to_string),
+            pattern: pattern.map(str::to_string),
+            format: format.map(str::to_string),
+            timezone: timezone.map(str::to_string),
+        })
+        .unwrap()
+    }
+
+    fn fields(value: Value) -> Fields {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn rfc3339_field() {
+        let extractor = extractor(Some("time"), None, None, None);
+        let timestamp = extractor.extract("", &fields(json!({"time": "2024-01-15T10:30:00.123+09:00"})));
+
+        assert_eq!(timestamp.unwrap().to_rfc3339(), "2024-01-15T01:30:00.123+00:00");
+    }
+
+    #[test]
+    fn epoch_field() {
+        let extractor = extractor(Some("ts"), None, None, None);
+
+        let seconds = extractor.extract("", &fields(json!({"ts": 1705282200})));
+        let millis = extractor.extract("", &fields(json!({"ts": 1705282200123_i64})));
+
+        assert_eq!(seconds.unwrap().timestamp(), 1705282200);
+        assert_eq!(millis.unwrap().timestamp_millis(), 1705282200123);
+    }
+
+    #[test]
+    fn pattern_with_format_and_named_timezone() {
+        let extractor = extractor(
+            None,
+            Some(r"^(\S+ \S+) "),
+            Some("%Y-%m-%d %H:%M:%S%.3f"),
+            Some("Asia/Seoul"),
+        );
+
+        let timestamp = extractor.extract("2024-01-15 10:30:00.123 INFO started", &Fields::new());
+
+        assert_eq!(timestamp.unwrap().to_rfc3339(), "2024-01-15T01:30:00.123+00:00");
+    }
+
+    #[test]
+    fn pattern_named_group_and_offset_in_value() {
+        let extractor = extractor(
+            None,
+            Some(r"\[(?P<timestamp>[^\]]+)\]"),
+            Some("%d/%b/%Y:%H:%M:%S %z"),
+            Some("+09:00"),
+        );
+
+        let timestamp = extractor.extract(r#"127.0.0.1 - - [10/Oct/2000:13:55:36 -0700] "GET / HTTP/1.0""#, &Fields::new());
+
+        assert_eq!(timestamp.unwrap().to_rfc3339(), "2000-10-10T20:55:36+00:00");
+    }
+
+    #[test]
+    fn format_without_year_uses_current_year() {
+        let extractor = extractor(None, Some(r"^(\w{3} [ \d]\d \d{2}:\d{2}:\d{2})"), Some("%b %e %H:%M:%S"), None);
+        let now = Utc::now();
+        let line = format!("{} host sshd[1]: accepted", (now - chrono::Duration::hours(1)).format("%b %e %H:%M:%S"));
+
+        let timestamp = extractor.extract(&line, &Fields::new()).unwrap();
+
+        assert!(now - timestamp >= chrono::Duration::minutes(59) && now - timestamp <= chrono::Duration::minutes(61));
+    }
+
+    #[test]
+    fn auto_formats_use_timezone() {
+        let extractor = extractor(Some("time"), None, None, Some("-05:00"));
+        let timestamp = extractor.extract("", &fields(json!({"time": "2024-01-15 10:30:00"})));
+
+        assert_eq!(timestamp.unwrap().to_rfc3339(), "2024-01-15T15:30:00+00:00");
+    }
+
+    #[test]
+    fn unparsable_value_is_none() {
+        let extractor = extractor(Some("time"), None, Some("%Y-%m-%d"), None);
+
+        assert!(extractor.extract("", &fields(json!({"time": "yesterday"}))).is_none());
+        assert!(extractor.extract("", &fields(json!({"other": "2024-01-15"}))).is_none());
+    }
+
+    #[test]
+    fn invalid_config_is_error() {
+        let config = |field: Option<&str>, pattern: Option<&str>, timezone: Option<&str>| TimestampConfig {
+            field: field.map(str::to_string),
+            pattern: pattern.map(str::to_string),
+            format: None,
+            timezone: timezone.map(str::to_string),
+        };
+
+        assert!(matches!(TimestampExtractor::build(&config(None, None, None)), Err(ParseError::MissingTimestampSource)));
+        assert!(matches!(TimestampExtractor::build(&config(Some("a"), Some("b"), None)), Err(ParseError::MissingTimestampSource)));
+        assert!(matches!(TimestampExtractor::build(&config(None, Some("("), None)), Err(ParseError::InvalidPattern(_))));
+        assert!(matches!(TimestampExtractor::build(&config(Some("a"), None, Some("Mars/Base"))), Err(ParseError::InvalidTimezone(_))));
+    }
+}
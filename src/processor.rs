@@ -0,0 +1,371 @@
+use std::{
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+use tokio::sync::mpsc::{error::SendError, Sender};
+use tracing::trace;
+
+use crate::{
+    config::{source_config::ProcessorConfig, LogFormat, SourceConfig},
+    filter::LineFilter,
+    log_event::LogEvent,
+    parser::LineParser,
+    redact::Redactor,
+    throttle::Throttle,
+};
+
+pub mod error;
+pub use error::ProcessorError;
+
+mod explode;
+use self::explode::Explode;
+
+mod failures;
+
+mod script;
+use self::script::ScriptProcessor;
+
+mod wasm;
+use self::wasm::WasmProcessor;
+
+// how often processors are asked for periodic events
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+pub enum Outcome {
+    Keep(LogEvent),
+    Drop,
+    Split(Vec<LogEvent>),
+}
+
+// one step between the detector and the aggregator
+pub trait Processor: Send {
+    fn process(&mut self, event: LogEvent) -> Outcome;
+
+    // events produced on their own, like drop summaries
+    fn flush(&mut self) -> Vec<LogEvent> {
+        Vec::new()
+    }
+}
+
+// the ordered processors of one source
+pub struct Pipeline {
+    processors: Vec<Box<dyn Processor>>,
+    keep_original: bool,
+}
+
+impl Pipeline {
+    pub fn build(source: &SourceConfig) -> Result<Self, ProcessorError> {
+        let order = match &source.processors {
+            Some(order) => {
+                Self::check_listed(source, order)?;
+                order.clone()
+            }
+            None => Self::default_order(source),
+        };
+
+        let mut processors: Vec<Box<dyn Processor>> = Vec::new();
+        for config in order {
+            processors.push(Self::build_processor(source, config)?);
+        }
+
+        Ok(Self {
+            processors,
+            keep_original: source.keep_original,
+        })
+    }
+
+    pub fn run(&mut self, event: LogEvent) -> Vec<LogEvent> {
+        let mut events = vec![event];
+
+        for processor in &mut self.processors {
+            let mut next = Vec::with_capacity(events.len());
+            for event in events {
+                match processor.process(event) {
+                    Outcome::Keep(event) => next.push(event),
+                    Outcome::Drop => {}
+                    Outcome::Split(events) => next.extend(events),
+                }
+            }
+
+            if next.is_empty() {
+                return next;
+            }
+            events = next;
+        }
+
+        // dropped only at the end so every processor still sees the raw line
+        if !self.keep_original {
+            events.iter_mut().filter(|event| !event.fields.is_empty()).for_each(|event| event.log.clear());
+        }
+
+        events
+    }
+
+    pub fn flush(&mut self) -> Vec<LogEvent> {
+        self.processors.iter_mut().flat_map(|processor| processor.flush()).collect()
+    }
+
+    fn default_order(source: &SourceConfig) -> Vec<ProcessorConfig> {
+        let configured = [
+            (ProcessorConfig::RateLimit, source.rate_limit.is_some()),
+            // drop filtered lines before paying for parsing, the level is only known after it
+            (ProcessorConfig::Filter, !source.include.is_empty() || !source.exclude.is_empty()),
+            (ProcessorConfig::Parse, is_parse_configured(source)),
+            (ProcessorConfig::LevelFilter, source.min_level.is_some()),
+            (ProcessorConfig::Sample, source.sampling.is_some()),
+            (ProcessorConfig::Redact, !source.redact.is_empty()),
+        ];
+
+        configured
+            .into_iter()
+            .filter(|(_, configured)| *configured)
+            .map(|(config, _)| config)
+            .collect()
+    }
+
+    fn check_listed(source: &SourceConfig, order: &[ProcessorConfig]) -> Result<(), ProcessorError> {
+        for config in Self::default_order(source) {
+            if !order.contains(&config) {
+                return Err(ProcessorError::NotListed(processor_name(&config)));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn build_processor(source: &SourceConfig, config: ProcessorConfig) -> Result<Box<dyn Processor>, ProcessorError> {
+        let name = processor_name(&config);
+        let not_configured = || ProcessorError::NotConfigured(name);
+
+        let processor: Box<dyn Processor> = match config {
+            ProcessorConfig::Parse => Box::new(LineParser::build(source)?.ok_or_else(not_configured)?),
+            ProcessorConfig::Filter => Box::new(LineFilter::lines(source)?.ok_or_else(not_configured)?),
+            ProcessorConfig::LevelFilter => Box::new(LineFilter::level(source)?.ok_or_else(not_configured)?),
+            ProcessorConfig::RateLimit => Box::new(Throttle::rate_limit(source).ok_or_else(not_configured)?),
+            ProcessorConfig::Sample => Box::new(Throttle::sampling(source).ok_or_else(not_configured)?),
+            ProcessorConfig::Redact => Box::new(Redactor::build(source)?.ok_or_else(not_configured)?),
+            ProcessorConfig::Explode { field } => Box::new(Explode::new(field)),
+            ProcessorConfig::Script { path, script, max_operations } => Box::new(ScriptProcessor::build(
+                &source.name,
+                path.as_deref(),
+                script.as_deref(),
+                max_operations,
+            )?),
+            ProcessorConfig::Wasm { path, fuel, max_memory_mb } => {
+                Box::new(WasmProcessor::build(&source.name, &path, fuel, max_memory_mb)?)
+            }
+        };
+
+        Ok(processor)
+    }
+}
+
+fn is_parse_configured(source: &SourceConfig) -> bool {
+    source.format != LogFormat::Raw || source.timestamp.is_some()
+}
+
+fn processor_name(config: &ProcessorConfig) -> &'static str {
+    match config {
+        ProcessorConfig::Parse => "parse",
+        ProcessorConfig::Filter => "filter",
+        ProcessorConfig::LevelFilter => "level_filter",
+        ProcessorConfig::RateLimit => "rate_limit",
+        ProcessorConfig::Sample => "sample",
+        ProcessorConfig::Redact => "redact",
+        ProcessorConfig::Explode { .. } => "explode",
+        ProcessorConfig::Script { .. } => "script",
+        ProcessorConfig::Wasm { .. } => "wasm",
+    }
+}
+
+// the pipeline of one source, run on the thread reading it before events enter the shared
+// channel. a noisy source only puts what survived its rate limit and filters into the channel,
+// and a slow script or plugin only holds up its own source. shared by the detectors a
+// supervisor restarts so token buckets and counters carry over
+#[derive(Clone)]
+pub struct SourceStage {
+    state: Arc<Mutex<StageState>>,
+    event_sender: Sender<LogEvent>,
+}
+
+struct StageState {
+    pipeline: Pipeline,
+    last_flush: Instant,
+}
+
+impl SourceStage {
+    pub fn new(pipeline: Pipeline, event_sender: Sender<LogEvent>) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(StageState { pipeline, last_flush: Instant::now() })),
+            event_sender,
+        }
+    }
+
+    // blocks while the channel is full, like the detector did before
+    pub fn send(&self, event: LogEvent) -> Result<(), Box<SendError<LogEvent>>> {
+        let events = {
+            let mut state = self.lock();
+            let mut events = state.pipeline.run(event);
+            events.extend(state.flush_due());
+            events
+        };
+
+        self.send_all(events)
+    }
+
+    // periodic events like drop summaries, readers call it while they wait for new lines
+    pub fn flush(&self) -> Result<(), Box<SendError<LogEvent>>> {
+        let events = self.lock().flush_due();
+        self.send_all(events)
+    }
+
+    // what the processors still hold once the source was read to the end
+    pub fn finish(&self) -> Result<(), Box<SendError<LogEvent>>> {
+        let events = self.lock().pipeline.flush();
+        self.send_all(events)
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.event_sender.is_closed()
+    }
+
+    fn send_all(&self, events: Vec<LogEvent>) -> Result<(), Box<SendError<LogEvent>>> {
+        for event in events {
+            trace!("[{}] processed event", event.name);
+            self.event_sender.blocking_send(event).map_err(Box::new)?;
+        }
+
+        Ok(())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, StageState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl StageState {
+    fn flush_due(&mut self) -> Vec<LogEvent> {
+        if self.last_flush.elapsed() < FLUSH_INTERVAL {
+            return Vec::new();
+        }
+
+        self.last_flush = Instant::now();
+        self.pipeline.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(options: &str) -> SourceConfig {
+        toml::from_str(&format!(
+            r#"
+                name = "app1"
+                log_path = "app1.log"
+                {options}
+            "#
+        ))
+        .unwrap()
+    }
+
+    fn event(line: &str) -> LogEvent {
+        LogEvent::new("app1".to_string(), line.to_string(), 0)
+    }
+
+    #[test]
+    fn default_order_of_configured_processors() {
+        let source = source(
+            r#"
+                format = "json"
+                exclude = ["healthz"]
+                min_level = "info"
+                redact = [{ builtin = "email" }]
+            "#,
+        );
+
+        assert_eq!(
+            Pipeline::default_order(&source),
+            vec![ProcessorConfig::Filter, ProcessorConfig::Parse, ProcessorConfig::LevelFilter, ProcessorConfig::Redact]
+        );
+
+        let mut pipeline = Pipeline::build(&source).unwrap();
+        assert!(pipeline.run(event(r#"{"level": "debug"}"#)).is_empty());
+        assert!(pipeline.run(event(r#"{"level": "error", "path": "/healthz"}"#)).is_empty());
+
+        let events = pipeline.run(event(r#"{"level": "error", "user": "a@example.com"}"#));
+        assert_eq!(events[0].fields["user"], "[REDACTED]");
+    }
+
+    #[test]
+    fn explicit_order_with_split() {
+        let source = source(
+            r#"
+                format = "json"
+                keep_original = false
+                exclude = ['"skip"']
+                processors = [
+                    { type = "parse" },
+                    { type = "explode", field = "records" },
+                    { type = "filter" },
+                ]
+            "#,
+        );
+        let mut pipeline = Pipeline::build(&source).unwrap();
+
+        let events = pipeline.run(event(r#"{"records": [{"id": "keep"}, {"id": "skip"}, {"id": "keep"}]}"#));
+
+        assert_eq!(events.len(), 2);
+        assert!(events.iter().all(|event| event.fields["id"] == "keep" && event.log.is_empty()));
+    }
+
+    #[test]
+    fn keep_original_clears_parsed_lines_only() {
+        let mut pipeline = Pipeline::build(&source(r#"format = "logfmt""#)).unwrap();
+        pipeline.keep_original = false;
+
+        let parsed = pipeline.run(event(r#"level=info msg="x y" dur=3ms"#)).remove(0);
+        let invalid = pipeline.run(event(r#"msg="broken"#)).remove(0);
+
+        assert_eq!(parsed.fields["msg"], "x y");
+        assert!(parsed.log.is_empty());
+        assert_eq!(invalid.log, r#"msg="broken"#);
+        assert!(invalid.parse_error);
+    }
+
+    #[test]
+    fn configured_processor_must_be_listed() {
+        let source = source(
+            r#"
+                format = "json"
+                redact = [{ builtin = "email" }]
+                processors = [{ type = "parse" }]
+            "#,
+        );
+
+        assert!(matches!(Pipeline::build(&source), Err(ProcessorError::NotListed("redact"))));
+    }
+
+    #[test]
+    fn listed_processor_must_be_configured() {
+        let source = source(r#"processors = [{ type = "sample" }]"#);
+
+        assert!(matches!(Pipeline::build(&source), Err(ProcessorError::NotConfigured("sample"))));
+    }
+
+    #[test]
+    fn throttled_source_does_not_fill_the_channel() {
+        let source = source("rate_limit = { lines_per_sec = 2 }");
+        let (event_sender, mut event_receiver) = tokio::sync::mpsc::channel(8);
+        let stage = SourceStage::new(Pipeline::build(&source).unwrap(), event_sender.clone());
+
+        // without the rate limit the 9th line would block on the full channel
+        for i in 0..1000 {
+            stage.send(event(&format!("line {i}"))).unwrap();
+        }
+
+        assert_eq!(event_receiver.len(), 2);
+        assert_eq!(event_sender.capacity(), 6);
+        assert_eq!(event_receiver.try_recv().unwrap().log, "line 0");
+    }
+}
@@ -0,0 +1,48 @@
+use std::fmt::{Display, Formatter};
+
+use crate::{filter::FilterError, parser::ParseError, redact::RedactError};
+
+#[derive(Debug)]
+pub enum ProcessorError {
+    InvalidParser(ParseError),
+    InvalidFilter(FilterError),
+    InvalidRedaction(RedactError),
+    // listed in `processors` without its options
+    NotConfigured(&'static str),
+    // options given but left out of `processors`, would silently be ignored
+    NotListed(&'static str),
+    InvalidScript(String),
+    InvalidWasm(String),
+}
+
+impl From<ParseError> for ProcessorError {
+    fn from(value: ParseError) -> Self {
+        ProcessorError::InvalidParser(value)
+    }
+}
+
+impl From<FilterError> for ProcessorError {
+    fn from(value: FilterError) -> Self {
+        ProcessorError::InvalidFilter(value)
+    }
+}
+
+impl From<RedactError> for ProcessorError {
+    fn from(value: RedactError) -> Self {
+        ProcessorError::InvalidRedaction(value)
+    }
+}
+
+impl Display for ProcessorError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProcessorError::InvalidParser(e) => write!(f, "{e}"),
+            ProcessorError::InvalidFilter(e) => write!(f, "{e}"),
+            ProcessorError::InvalidRedaction(e) => write!(f, "{e}"),
+            ProcessorError::NotConfigured(processor) => write!(f, "Processor '{processor}' is listed but not configured"),
+            ProcessorError::NotListed(processor) => write!(f, "Processor '{processor}' is configured but not listed in 'processors'"),
+            ProcessorError::InvalidScript(e) => write!(f, "Invalid script: {e}"),
+            ProcessorError::InvalidWasm(e) => write!(f, "Invalid wasm module: {e}"),
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use serde_json::Value;
+use std::mem;
+
+use crate::{
+    log_event::LogEvent,
+    processor::{Outcome, Processor},
+};
+
+// splits an event with an array field, like the `Records` of a batched JSON line, into one event per element
+pub struct Explode {
+    field: String,
+}
+
+impl Explode {
+    pub fn new(field: String) -> Self {
+        Self { field }
+    }
+}
+
+impl Processor for Explode {
+    fn process(&mut self, mut event: LogEvent) -> Outcome {
+        let elements = match event.fields.remove(&self.field) {
+            Some(Value::Array(elements)) => elements,
+            Some(other) => {
+                event.fields.insert(self.field.clone(), other);
+                return Outcome::Keep(event);
+            }
+            None => return Outcome::Keep(event),
+        };
+
+        // the other fields are shared, an object element is merged into them
+        let shared = mem::take(&mut event.fields);
+        event.log.clear();
+
+        let events = elements
+            .into_iter()
+            .map(|element| {
+                let log = element.to_string();
+                let mut fields = shared.clone();
+                match element {
+                    Value::Object(element) => fields.extend(element),
+                    element => {
+                        fields.insert(self.field.clone(), element);
+                    }
+                }
+
+                LogEvent { log, fields, ..event.clone() }
+            })
+            .collect();
+
+        Outcome::Split(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn event(fields: Value) -> LogEvent {
+        let mut event = LogEvent::new("app1".to_string(), fields.to_string(), 10);
+        event.fields = fields.as_object().unwrap().clone();
+        event
+    }
+
+    #[test]
+    fn one_event_per_element() {
+        let mut explode = Explode::new("Records".to_string());
+        let event = event(json!({"region": "eu", "Records": [{"id": 1}, {"id": 2}, "x"]}));
+
+        let Outcome::Split(events) = explode.process(event) else { panic!("expected split") };
+
+        assert_eq!(events.len(), 3);
+        assert_eq!(Value::Object(events[0].fields.clone()), json!({"region": "eu", "id": 1}));
+        assert_eq!(events[0].log, r#"{"id":1}"#);
+        assert_eq!(Value::Object(events[2].fields.clone()), json!({"region": "eu", "Records": "x"}));
+        assert!(events.iter().all(|event| event.offset == 10));
+    }
+
+    #[test]
+    fn keeps_event_without_array() {
+        let mut explode = Explode::new("Records".to_string());
+
+        let Outcome::Keep(event) = explode.process(event(json!({"Records": "none"}))) else { panic!("expected keep") };
+        assert_eq!(event.fields["Records"], "none");
+    }
+}
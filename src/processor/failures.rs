@@ -0,0 +1,61 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tracing::{debug, warn};
+
+use crate::metrics::{metrics, SourceMetrics};
+
+const REPORT_INTERVAL: Duration = Duration::from_secs(10);
+
+// counts failures of a processor that passes events on instead of stopping the source
+pub struct Failures {
+    source_name: String,
+    kind: &'static str,
+    pub count: u64,
+    reported: u64,
+    pub last_error: Option<String>,
+    last_report: Instant,
+    metrics: Arc<SourceMetrics>,
+}
+
+impl Failures {
+    pub fn new(source_name: &str, kind: &'static str) -> Self {
+        Self {
+            source_name: source_name.to_string(),
+            kind,
+            count: 0,
+            reported: 0,
+            last_error: None,
+            last_report: Instant::now(),
+            metrics: metrics().source(source_name),
+        }
+    }
+
+    pub fn record(&mut self, error: String) {
+        debug!("[{}] {} failed: {error}", self.source_name, self.kind);
+        self.count += 1;
+        self.metrics.processor_failed(self.kind);
+        self.last_error = Some(error);
+    }
+
+    // summarized in one warning at most every interval
+    pub fn report(&mut self) {
+        if self.last_report.elapsed() < REPORT_INTERVAL || self.count == self.reported {
+            return;
+        }
+
+        warn!(
+            "[{}] {} failed {} times in last {}s (total: {}), last error: {}",
+            self.source_name,
+            self.kind,
+            self.count - self.reported,
+            self.last_report.elapsed().as_secs(),
+            self.count,
+            self.last_error.as_deref().unwrap_or_default(),
+        );
+
+        self.reported = self.count;
+        self.last_report = Instant::now();
+    }
+}
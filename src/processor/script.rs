@@ -0,0 +1,245 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use rhai::{module_resolvers::DummyModuleResolver, Dynamic, Engine, Map, Scope, AST};
+use std::fs;
+use tracing::debug;
+
+use crate::{
+    log_event::LogEvent,
+    parser::Fields,
+    processor::{failures::Failures, Outcome, Processor, ProcessorError},
+};
+
+const ENTRY_POINT: &str = "process";
+
+// runs `fn process(event)` of a Rhai script, the script can change the event map,
+// return `()` to drop it or an array of maps to emit several events
+pub struct ScriptProcessor {
+    engine: Engine,
+    ast: AST,
+    failures: Failures,
+}
+
+impl ScriptProcessor {
+    pub fn build(
+        source_name: &str,
+        path: Option<&str>,
+        script: Option<&str>,
+        max_operations: u64,
+    ) -> Result<Self, ProcessorError> {
+        let script = match (path, script) {
+            (Some(path), None) => fs::read_to_string(path)
+                .map_err(|e| ProcessorError::InvalidScript(format!("can not read '{path}': {e}")))?,
+            (None, Some(script)) => script.to_string(),
+            _ => return Err(ProcessorError::InvalidScript("needs exactly one of 'path' or 'script'".to_string())),
+        };
+
+        let engine = Self::sandboxed_engine(source_name, max_operations);
+        let ast = engine
+            .compile(&script)
+            .map_err(|e| ProcessorError::InvalidScript(e.to_string()))?;
+
+        if !ast.iter_functions().any(|f| f.name == ENTRY_POINT && f.params.len() == 1) {
+            return Err(ProcessorError::InvalidScript(format!("missing 'fn {ENTRY_POINT}(event)'")));
+        }
+
+        Ok(Self {
+            engine,
+            ast,
+            failures: Failures::new(source_name, "script"),
+        })
+    }
+
+    // Rhai has no file or network access of its own, imports and eval are closed as well
+    fn sandboxed_engine(source_name: &str, max_operations: u64) -> Engine {
+        let mut engine = Engine::new();
+        engine.set_max_operations(max_operations);
+        engine.set_max_call_levels(32);
+        engine.set_max_expr_depths(64, 32);
+        engine.set_max_string_size(1024 * 1024);
+        engine.set_max_array_size(10_000);
+        engine.set_max_map_size(10_000);
+        engine.set_module_resolver(DummyModuleResolver::new());
+        engine.disable_symbol("eval");
+
+        let name = source_name.to_string();
+        engine.on_print(move |text| debug!("[{name}] script: {text}"));
+        let name = source_name.to_string();
+        engine.on_debug(move |text, _, _| debug!("[{name}] script: {text}"));
+
+        engine
+    }
+
+    fn run(&self, event: &LogEvent) -> Result<Vec<LogEvent>, String> {
+        let result: Dynamic = self
+            .engine
+            .call_fn(&mut Scope::new(), &self.ast, ENTRY_POINT, (to_map(event)?,))
+            .map_err(|e| e.to_string())?;
+
+        if result.is_unit() {
+            return Ok(Vec::new());
+        }
+
+        if result.is_array() {
+            return result
+                .into_array()?
+                .into_iter()
+                .map(|item| from_map(event, item))
+                .collect();
+        }
+
+        Ok(vec![from_map(event, result)?])
+    }
+}
+
+fn to_map(event: &LogEvent) -> Result<Map, String> {
+    let mut map = Map::new();
+    map.insert("source".into(), event.name.clone().into());
+    map.insert("line".into(), event.log.clone().into());
+    map.insert("timestamp".into(), event.timestamp.to_rfc3339_opts(SecondsFormat::Millis, true).into());
+    map.insert("fields".into(), rhai::serde::to_dynamic(&event.fields).map_err(|e| e.to_string())?);
+
+    Ok(map)
+}
+
+// keys left out of the map keep the values of the original event
+fn from_map(original: &LogEvent, value: Dynamic) -> Result<LogEvent, String> {
+    let type_name = value.type_name();
+    let map = value
+        .try_cast::<Map>()
+        .ok_or_else(|| format!("script must return a map, an array of maps or (), got {type_name}"))?;
+
+    let mut event = original.clone();
+    if let Some(line) = map.get("line") {
+        event.log = line.clone().into_string()?;
+    }
+
+    if let Some(timestamp) = map.get("timestamp") {
+        let timestamp = timestamp.clone().into_string()?;
+        event.timestamp = DateTime::parse_from_rfc3339(&timestamp)
+            .map_err(|e| format!("invalid timestamp '{timestamp}': {e}"))?
+            .with_timezone(&Utc);
+    }
+
+    if let Some(fields) = map.get("fields") {
+        event.fields = rhai::serde::from_dynamic::<Fields>(fields).map_err(|e| e.to_string())?;
+    }
+
+    Ok(event)
+}
+
+impl Processor for ScriptProcessor {
+    // a failing script must not lose the line, the event passes unchanged
+    fn process(&mut self, event: LogEvent) -> Outcome {
+        match self.run(&event) {
+            Ok(mut events) if events.len() == 1 => Outcome::Keep(events.remove(0)),
+            Ok(events) if events.is_empty() => Outcome::Drop,
+            Ok(events) => Outcome::Split(events),
+            Err(e) => {
+                self.failures.record(e);
+                Outcome::Keep(event)
+            }
+        }
+    }
+
+    fn flush(&mut self) -> Vec<LogEvent> {
+        self.failures.report();
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn processor(script: &str) -> ScriptProcessor {
+        ScriptProcessor::build("app1", None, Some(script), 10_000).unwrap()
+    }
+
+    fn event(line: &str) -> LogEvent {
+        LogEvent::new("app1".to_string(), line.to_string(), 7)
+    }
+
+    #[test]
+    fn modifies_event() {
+        let mut processor = processor(
+            r#"
+                fn process(event) {
+                    let parts = event.line.split("|");
+                    event.fields.user = parts[0];
+                    event.fields.took_ms = parse_int(parts[1]);
+                    event.timestamp = "2024-01-15T10:30:00Z";
+                    event
+                }
+            "#,
+        );
+
+        let Outcome::Keep(event) = processor.process(event("alice|42")) else { panic!("expected keep") };
+
+        assert_eq!(event.log, "alice|42");
+        assert_eq!(serde_json::Value::Object(event.fields), json!({"user": "alice", "took_ms": 42}));
+        assert_eq!(event.timestamp.to_rfc3339(), "2024-01-15T10:30:00+00:00");
+        assert_eq!(event.offset, 7);
+    }
+
+    #[test]
+    fn drops_and_emits() {
+        let mut processor = processor(
+            r##"
+                fn process(event) {
+                    if event.line.starts_with("#") { return (); }
+                    event.line.split(";").map(|part| #{ line: part })
+                }
+            "##,
+        );
+
+        assert!(matches!(processor.process(event("# comment")), Outcome::Drop));
+
+        let Outcome::Split(events) = processor.process(event("a;b;c")) else { panic!("expected split") };
+        let lines: Vec<&str> = events.iter().map(|event| event.log.as_str()).collect();
+        assert_eq!(lines, vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn errors_are_counted_and_event_is_kept() {
+        let mut processor = processor(
+            r#"
+                fn process(event) {
+                    if event.line == "loop" { loop {} }
+                    event.fields.x = event.line.len() / 0;
+                    event
+                }
+            "#,
+        );
+
+        let Outcome::Keep(event) = processor.process(event("loop")) else { panic!("expected keep") };
+        assert_eq!(event.log, "loop");
+        assert!(matches!(processor.process(self::event("boom")), Outcome::Keep(_)));
+
+        assert_eq!(processor.failures.count, 2);
+        assert!(processor.failures.last_error.is_some());
+    }
+
+    #[test]
+    fn sandbox_blocks_eval_and_import() {
+        let eval = ScriptProcessor::build("app1", None, Some(r#"fn process(event) { eval("1") }"#), 10_000);
+        assert!(matches!(eval, Err(ProcessorError::InvalidScript(_))));
+
+        let mut import = processor(
+            r#"
+                fn process(event) {
+                    import "/etc/passwd" as secrets;
+                    event
+                }
+            "#,
+        );
+        assert!(matches!(import.process(event("x")), Outcome::Keep(_)));
+        assert_eq!(import.failures.count, 1);
+    }
+
+    #[test]
+    fn entry_point_is_required() {
+        let result = ScriptProcessor::build("app1", None, Some("fn transform(event) { event }"), 10_000);
+        assert!(matches!(result, Err(ProcessorError::InvalidScript(_))));
+    }
+}
@@ -0,0 +1,306 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::{json, Value};
+use std::{fs, time::SystemTime};
+use tracing::{info, warn};
+use wasmi::{Config, Engine, Linker, Module, Store, StoreLimits, StoreLimitsBuilder};
+
+use crate::{
+    log_event::LogEvent,
+    processor::{failures::Failures, Outcome, Processor, ProcessorError},
+};
+
+// runs a WebAssembly module per event, the module exports
+//   memory
+//   alloc(len: i32) -> i32                  space for the input
+//   process(ptr: i32, len: i32) -> i64      (ptr << 32) | len of the output, 0 keeps the event
+// input and output are JSON, like the map of the script processor
+pub struct WasmProcessor {
+    source_name: String,
+    path: String,
+    modified: Option<SystemTime>,
+    engine: Engine,
+    module: Module,
+    fuel: u64,
+    max_memory_bytes: usize,
+    failures: Failures,
+}
+
+impl WasmProcessor {
+    pub fn build(source_name: &str, path: &str, fuel: u64, max_memory_mb: usize) -> Result<Self, ProcessorError> {
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+
+        let module = Self::load(&engine, path).map_err(ProcessorError::InvalidWasm)?;
+
+        Ok(Self {
+            source_name: source_name.to_string(),
+            path: path.to_string(),
+            modified: Self::modified(path),
+            engine,
+            module,
+            fuel,
+            max_memory_bytes: max_memory_mb * 1024 * 1024,
+            failures: Failures::new(source_name, "wasm"),
+        })
+    }
+
+    // nothing is linked, a module importing anything (like WASI) can not reach the host
+    fn load(engine: &Engine, path: &str) -> Result<Module, String> {
+        let wasm = fs::read(path).map_err(|e| format!("can not read '{path}': {e}"))?;
+        let module = Module::new(engine, &wasm).map_err(|e| e.to_string())?;
+
+        if let Some(import) = module.imports().next() {
+            return Err(format!("imports are not allowed: {}.{}", import.module(), import.name()));
+        }
+
+        Ok(module)
+    }
+
+    fn modified(path: &str) -> Option<SystemTime> {
+        fs::metadata(path).and_then(|meta| meta.modified()).ok()
+    }
+
+    // a broken new module keeps the old one running
+    fn reload_if_changed(&mut self) {
+        let modified = Self::modified(&self.path);
+        if modified.is_none() || modified == self.modified {
+            return;
+        }
+        self.modified = modified;
+
+        match Self::load(&self.engine, &self.path) {
+            Ok(module) => {
+                info!("[{}] reloaded wasm module {}", self.source_name, self.path);
+                self.module = module;
+            }
+            Err(e) => warn!("[{}] failed to reload wasm module {}: {e}", self.source_name, self.path),
+        }
+    }
+
+    // a fresh instance per call, so fuel and memory limits apply to one invocation and nothing leaks between events
+    fn run(&self, event: &LogEvent) -> Result<Option<Vec<LogEvent>>, String> {
+        let limits = StoreLimitsBuilder::new().memory_size(self.max_memory_bytes).build();
+        let mut store = Store::new(&self.engine, limits);
+        store.limiter(|limits: &mut StoreLimits| limits);
+        store.set_fuel(self.fuel).map_err(|e| e.to_string())?;
+
+        let instance = Linker::<StoreLimits>::new(&self.engine)
+            .instantiate(&mut store, &self.module)
+            .and_then(|pre| pre.start(&mut store))
+            .map_err(|e| e.to_string())?;
+
+        let memory = instance.get_memory(&store, "memory").ok_or("missing 'memory' export")?;
+        let alloc = instance.get_typed_func::<i32, i32>(&store, "alloc").map_err(|e| e.to_string())?;
+        let process = instance.get_typed_func::<(i32, i32), i64>(&store, "process").map_err(|e| e.to_string())?;
+
+        let input = serde_json::to_vec(&json!({
+            "source": event.name,
+            "line": event.log,
+            "timestamp": event.timestamp.to_rfc3339_opts(SecondsFormat::Millis, true),
+            "fields": event.fields,
+        }))
+        .map_err(|e| e.to_string())?;
+        let input_len = i32::try_from(input.len()).map_err(|_| "event is too large")?;
+
+        let ptr = alloc.call(&mut store, input_len).map_err(|e| e.to_string())?;
+        memory.write(&mut store, ptr as u32 as usize, &input).map_err(|e| e.to_string())?;
+
+        let result = process.call(&mut store, (ptr, input_len)).map_err(|e| e.to_string())? as u64;
+        if result == 0 {
+            return Ok(None);
+        }
+
+        // the length comes from the module, it is checked against the memory before anything is read
+        let (output_ptr, output_len) = ((result >> 32) as usize, (result & 0xffff_ffff) as usize);
+        let data = memory.data(&store);
+        let output = output_ptr
+            .checked_add(output_len)
+            .and_then(|end| data.get(output_ptr..end))
+            .ok_or_else(|| format!("output of {output_len} bytes at {output_ptr} is outside the memory of {} bytes", data.len()))?;
+
+        let output: Value = serde_json::from_slice(output).map_err(|e| format!("invalid output: {e}"))?;
+        match output {
+            Value::Null => Ok(Some(Vec::new())),
+            Value::Array(items) => items.into_iter().map(|item| from_json(event, item)).collect::<Result<_, _>>().map(Some),
+            item => from_json(event, item).map(|event| Some(vec![event])),
+        }
+    }
+}
+
+// keys left out of the output keep the values of the original event
+fn from_json(original: &LogEvent, value: Value) -> Result<LogEvent, String> {
+    let Value::Object(mut output) = value else {
+        return Err("output must be an object, an array of objects or null".to_string());
+    };
+
+    let mut event = original.clone();
+    match output.remove("line") {
+        Some(Value::String(line)) => event.log = line,
+        Some(_) => return Err("'line' must be a string".to_string()),
+        None => {}
+    }
+
+    match output.remove("timestamp") {
+        Some(Value::String(timestamp)) => {
+            event.timestamp = DateTime::parse_from_rfc3339(&timestamp)
+                .map_err(|e| format!("invalid timestamp '{timestamp}': {e}"))?
+                .with_timezone(&Utc);
+        }
+        Some(_) => return Err("'timestamp' must be a string".to_string()),
+        None => {}
+    }
+
+    match output.remove("fields") {
+        Some(Value::Object(fields)) => event.fields = fields,
+        Some(_) => return Err("'fields' must be an object".to_string()),
+        None => {}
+    }
+
+    Ok(event)
+}
+
+impl Processor for WasmProcessor {
+    // a failing module must not lose the line, the event passes unchanged
+    fn process(&mut self, event: LogEvent) -> Outcome {
+        match self.run(&event) {
+            Ok(None) => Outcome::Keep(event),
+            Ok(Some(mut events)) if events.len() == 1 => Outcome::Keep(events.remove(0)),
+            Ok(Some(events)) if events.is_empty() => Outcome::Drop,
+            Ok(Some(events)) => Outcome::Split(events),
+            Err(e) => {
+                self.failures.record(e);
+                Outcome::Keep(event)
+            }
+        }
+    }
+
+    fn flush(&mut self) -> Vec<LogEvent> {
+        self.reload_if_changed();
+        self.failures.report();
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempPath;
+    use std::{path::Path, time::Duration};
+
+    // module returning the constant output at offset 0, `output` must not contain quotes of WAT
+    fn constant_module(output: &str) -> String {
+        format!(
+            r#"
+            (module
+              (memory (export "memory") 1)
+              (data (i32.const 0) "{}")
+              (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+              (func (export "process") (param i32 i32) (result i64) (i64.const {})))
+            "#,
+            output.replace('"', "\\\""),
+            output.len()
+        )
+    }
+
+    fn write_module(name: &str, wat: &str) -> TempPath {
+        let path = TempPath::new(&format!("{name}.wasm"));
+        fs::write(&path, wat::parse_str(wat).unwrap()).unwrap();
+        path
+    }
+
+    fn processor(path: &Path) -> WasmProcessor {
+        WasmProcessor::build("app1", path.to_str().unwrap(), 100_000, 1).unwrap()
+    }
+
+    fn event(line: &str) -> LogEvent {
+        LogEvent::new("app1".to_string(), line.to_string(), 7)
+    }
+
+    #[test]
+    fn sample_module_drops_debug() {
+        let sample = fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/plugins/drop_debug.wat")).unwrap();
+        let path = write_module("drop-debug", &sample);
+        let mut processor = processor(&path);
+
+        assert!(matches!(processor.process(event("DEBUG cache miss")), Outcome::Drop));
+        let Outcome::Keep(kept) = processor.process(event("ERROR db down")) else { panic!("expected keep") };
+        assert_eq!(kept.log, "ERROR db down");
+        assert_eq!(processor.failures.count, 0);
+    }
+
+    #[test]
+    fn modifies_and_splits() {
+        let modify = write_module("modify", &constant_module(r#"{"line":"changed","fields":{"plugin":"wasm"}}"#));
+        let Outcome::Keep(event) = processor(&modify).process(event("original")) else { panic!("expected keep") };
+        assert_eq!(event.log, "changed");
+        assert_eq!(event.fields["plugin"], "wasm");
+        assert_eq!(event.offset, 7);
+
+        let split = write_module("split", &constant_module(r#"[{"line":"a"},{"line":"b"}]"#));
+        let Outcome::Split(events) = processor(&split).process(self::event("a b")) else { panic!("expected split") };
+        assert_eq!(events.iter().map(|e| e.log.as_str()).collect::<Vec<_>>(), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn fuel_and_memory_are_limited() {
+        let endless = write_module(
+            "endless",
+            r#"
+            (module
+              (memory (export "memory") 1)
+              (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+              (func (export "process") (param i32 i32) (result i64) (loop $l (br $l)) (i64.const 0)))
+            "#,
+        );
+        let mut processor = processor(&endless);
+        let Outcome::Keep(event) = processor.process(event("x")) else { panic!("expected keep") };
+        assert_eq!(event.log, "x");
+        assert_eq!(processor.failures.count, 1);
+
+        // 2 pages over the 1 MiB limit
+        let greedy = write_module("greedy", &constant_module("null").replace("(memory (export \"memory\") 1)", "(memory (export \"memory\") 17)"));
+        let mut processor = self::processor(&greedy);
+        assert!(matches!(processor.process(self::event("x")), Outcome::Keep(_)));
+        assert_eq!(processor.failures.count, 1);
+    }
+
+    #[test]
+    fn output_outside_memory_is_error() {
+        // 4 GiB - 1 bytes at offset 0, one page of memory
+        let path = write_module("outside", &constant_module("{}").replace("(i64.const 2)", "(i64.const 4294967295)"));
+        let mut processor = processor(&path);
+
+        let Outcome::Keep(event) = processor.process(event("x")) else { panic!("expected keep") };
+        assert_eq!(event.log, "x");
+        assert_eq!(processor.failures.count, 1);
+    }
+
+    #[test]
+    fn imports_are_rejected() {
+        let path = write_module(
+            "imports",
+            r#"(module (import "wasi_snapshot_preview1" "fd_write" (func (param i32 i32 i32 i32) (result i32))))"#,
+        );
+
+        let result = WasmProcessor::build("app1", path.to_str().unwrap(), 100_000, 1);
+        assert!(matches!(result, Err(ProcessorError::InvalidWasm(_))));
+    }
+
+    #[test]
+    fn reloads_changed_module() {
+        let path = write_module("reload", &constant_module(r#"{"line":"v1"}"#));
+        let mut processor = processor(&path);
+
+        let Outcome::Keep(event) = processor.process(event("x")) else { panic!("expected keep") };
+        assert_eq!(event.log, "v1");
+
+        fs::write(&path, wat::parse_str(constant_module(r#"{"line":"v2"}"#)).unwrap()).unwrap();
+        let file = fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(5)).unwrap();
+        processor.flush();
+
+        let Outcome::Keep(event) = processor.process(self::event("x")) else { panic!("expected keep") };
+        assert_eq!(event.log, "v2");
+    }
+}
@@ -0,0 +1,223 @@
+use regex::{Captures, Regex};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+
+use crate::{
+    config::{source_config::{RedactBuiltin, RedactRule}, SourceConfig},
+    log_event::LogEvent,
+    processor::{Outcome, Processor},
+};
+
+pub mod error;
+pub use error::RedactError;
+
+mod builtin;
+
+// hex characters of the sha256 kept in place of a hashed value
+const HASH_LEN: usize = 16;
+
+struct CompiledRule {
+    regex: Regex,
+    builtin: Option<RedactBuiltin>,
+    replacement: String,
+    hash: bool,
+    salt: String,
+}
+
+// masks sensitive values of one source in the line and in parsed fields
+pub struct Redactor {
+    rules: Vec<CompiledRule>,
+}
+
+impl Redactor {
+    pub fn build(source: &SourceConfig) -> Result<Option<Self>, RedactError> {
+        if source.redact.is_empty() {
+            return Ok(None);
+        }
+
+        let rules = source.redact.iter().map(CompiledRule::build).collect::<Result<_, _>>()?;
+        Ok(Some(Self { rules }))
+    }
+
+    pub fn apply(&self, event: &mut LogEvent) {
+        if let Cow::Owned(log) = self.redact(&event.log) {
+            event.log = log;
+        }
+
+        for value in event.fields.values_mut() {
+            self.redact_value(value);
+        }
+    }
+
+    fn redact_value(&self, value: &mut Value) {
+        match value {
+            Value::String(text) => {
+                if let Cow::Owned(redacted) = self.redact(text) {
+                    *text = redacted;
+                }
+            }
+            Value::Array(values) => values.iter_mut().for_each(|value| self.redact_value(value)),
+            Value::Object(fields) => fields.values_mut().for_each(|value| self.redact_value(value)),
+            _ => {}
+        }
+    }
+
+    fn redact<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        self.rules.iter().fold(Cow::Borrowed(text), |text, rule| match rule.redact(&text) {
+            Cow::Owned(redacted) => Cow::Owned(redacted),
+            Cow::Borrowed(_) => text,
+        })
+    }
+}
+
+impl Processor for Redactor {
+    fn process(&mut self, mut event: LogEvent) -> Outcome {
+        self.apply(&mut event);
+        Outcome::Keep(event)
+    }
+}
+
+impl CompiledRule {
+    fn build(rule: &RedactRule) -> Result<Self, RedactError> {
+        let pattern = match (rule.builtin, rule.pattern.as_deref()) {
+            (Some(builtin), None) => builtin::pattern(builtin),
+            (None, Some(pattern)) => pattern,
+            _ => return Err(RedactError::AmbiguousRule),
+        };
+
+        Ok(Self {
+            regex: Regex::new(pattern)?,
+            builtin: rule.builtin,
+            replacement: rule.replacement.clone(),
+            hash: rule.hash,
+            salt: rule.salt.clone(),
+        })
+    }
+
+    fn redact<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        self.regex.replace_all(text, |caps: &Captures| {
+            let matched = &caps[0];
+
+            let range = match self.builtin {
+                Some(builtin) => match builtin::validate(builtin, matched) {
+                    Some(range) => range,
+                    None => return matched.to_string(),
+                },
+                None => 0..matched.len(),
+            };
+
+            let replaced = if self.hash {
+                self.hash(&matched[range.clone()])
+            } else {
+                let mut replaced = String::new();
+                caps.expand(&self.replacement, &mut replaced);
+                replaced
+            };
+            format!("{}{replaced}{}", &matched[..range.start], &matched[range.end..])
+        })
+    }
+
+    fn hash(&self, value: &str) -> String {
+        let digest = Sha256::new()
+            .chain_update(self.salt.as_bytes())
+            .chain_update(value.as_bytes())
+            .finalize();
+
+        let mut hex: String = digest.iter().map(|byte| format!("{byte:02x}")).collect();
+        hex.truncate(HASH_LEN);
+        hex
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn redactor(rules: &str) -> Redactor {
+        let source: SourceConfig = toml::from_str(&format!(
+            r#"
+                name = "app1"
+                log_path = "app1.log"
+                {rules}
+            "#
+        ))
+        .unwrap();
+
+        Redactor::build(&source).unwrap().unwrap()
+    }
+
+    fn event(line: &str) -> LogEvent {
+        LogEvent::new("app1".to_string(), line.to_string(), 0)
+    }
+
+    #[test]
+    fn builtin_and_pattern_rules() {
+        let redactor = redactor(
+            r#"
+                redact = [
+                    { builtin = "email", replacement = "[EMAIL]" },
+                    { builtin = "credit_card" },
+                    { pattern = '(token=)\w+', replacement = '${1}***' },
+                ]
+            "#,
+        );
+
+        let mut event = event("login john@example.com card 4111-1111-1111-1111 order 4111111111111112 token=abc123");
+        redactor.apply(&mut event);
+
+        assert_eq!(event.log, "login [EMAIL] card [REDACTED] order 4111111111111112 token=***");
+
+        let mut event = self::event("card 4111111111111111 123");
+        redactor.apply(&mut event);
+        assert_eq!(event.log, "card [REDACTED] 123");
+    }
+
+    #[test]
+    fn redacts_nested_fields() {
+        let redactor = redactor(r#"redact = [{ builtin = "ip" }]"#);
+
+        let mut event = event("");
+        event.fields = json!({"client": "10.0.0.1", "hops": ["10.0.0.2", 3], "req": {"peer": "::1"}, "status": 200})
+            .as_object()
+            .unwrap()
+            .clone();
+        redactor.apply(&mut event);
+
+        assert_eq!(
+            Value::Object(event.fields),
+            json!({"client": "[REDACTED]", "hops": ["[REDACTED]", 3], "req": {"peer": "[REDACTED]"}, "status": 200})
+        );
+    }
+
+    #[test]
+    fn hash_keeps_equal_values_equal() {
+        let redactor = redactor(r#"redact = [{ builtin = "email", hash = true, salt = "s3cret" }]"#);
+
+        let mut first = event("from a@example.com to b@example.com");
+        let mut second = event("from a@example.com");
+        redactor.apply(&mut first);
+        redactor.apply(&mut second);
+
+        let hashes: Vec<&str> = first.log.split(' ').collect();
+        assert_eq!(hashes[1].len(), HASH_LEN);
+        assert_ne!(hashes[1], hashes[3]);
+        assert_eq!(second.log, format!("from {}", hashes[1]));
+        assert!(!first.log.contains("example.com"));
+    }
+
+    #[test]
+    fn rule_needs_one_of_builtin_or_pattern() {
+        let source: SourceConfig = toml::from_str(
+            r#"
+                name = "app1"
+                log_path = "app1.log"
+                redact = [{ builtin = "email", pattern = "x" }]
+            "#,
+        )
+        .unwrap();
+
+        assert!(matches!(Redactor::build(&source), Err(RedactError::AmbiguousRule)));
+    }
+}
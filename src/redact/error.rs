@@ -0,0 +1,23 @@
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug)]
+pub enum RedactError {
+    InvalidPattern(regex::Error),
+    // neither or both of builtin and pattern
+    AmbiguousRule,
+}
+
+impl From<regex::Error> for RedactError {
+    fn from(value: regex::Error) -> Self {
+        RedactError::InvalidPattern(value)
+    }
+}
+
+impl Display for RedactError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RedactError::InvalidPattern(e) => write!(f, "Invalid redact pattern: {e}"),
+            RedactError::AmbiguousRule => write!(f, "Redact rule needs exactly one of 'builtin' or 'pattern'"),
+        }
+    }
+}
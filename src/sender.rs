@@ -1,10 +1,14 @@
 use async_trait::async_trait;
-use std::sync::Arc;
-use tokio::sync::mpsc::Receiver;
+use std::{collections::HashSet, sync::{Arc, Weak}};
+use tokio::sync::mpsc::{self, error::TrySendError, Receiver};
 use tokio::sync::Semaphore;
 use tokio::task;
+use tracing::{trace, warn};
 
-use crate::config::global_config;
+use crate::{
+    config::{global_config, OutputConfig, OverflowPolicy},
+    metrics::metrics,
+};
 
 pub mod payload;
 use self::payload::Payload;
@@ -12,18 +16,145 @@ use self::payload::Payload;
 mod error;
 pub use error::SenderError;
 
+pub mod delivery;
+use self::delivery::{Delivery, DeliveryOutcome, DeliveryReport, SourceSequences};
+
+mod circuit_breaker;
+use self::circuit_breaker::CircuitBreakerSender;
+
+mod router;
+use self::router::Route;
+
+mod retry_scheduler;
+mod spool;
 mod strategies;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SendResult {
+    Delivered,
+    // server refused the payload, sending it again will not help
+    Rejected,
+    // network or server failure, the strategy may still retry it
+    Unreachable,
+    // buffered without touching the network
+    Spooled,
+}
+
+// send returns the result of the first attempt right away, the final outcome
+// (including retries) is reported through the delivery handle
 #[async_trait]
 pub trait Sender: Send + Sync {
-    async fn send(&self, payload: Payload);
+    async fn send(&self, payload: Payload, delivery: Delivery) -> SendResult;
+
+    // called once every payload of the output was sent
+    async fn shutdown(&self) {}
+
+    // a strategy that retries on its own asks the guard before every retry
+    fn guard_retries(&self, _guard: Weak<dyn RetryGuard>) {}
+}
+
+// decides whether a retry may reach the network, the circuit breaker spools it otherwise
+pub trait RetryGuard: Send + Sync {
+    // gives the payload back when the retry may go ahead
+    fn hold(&self, payload: Payload, delivery: Delivery) -> Option<(Payload, Delivery)>;
 }
-pub fn spawn_sender(mut payload_receiver: Receiver<Payload>) -> Result<task::JoinHandle<()>, SenderError> {
-    let sender = strategies::build_sender()?;
-    let semaphore = Arc::new(Semaphore::new(global_config().max_send_task as usize));
+
+pub fn spawn_sender(
+    mut payload_receiver: Receiver<Payload>,
+    outputs: Vec<OutputConfig>,
+    report_sender: mpsc::UnboundedSender<DeliveryReport>,
+) -> Result<task::JoinHandle<()>, SenderError> {
+    let channel_bound = global_config().channel_bound;
+
+    let mut routes = Vec::with_capacity(outputs.len());
+    let mut output_handles = Vec::with_capacity(outputs.len());
+    for output in &outputs {
+        let sender = CircuitBreakerSender::wrap(strategies::build_sender(output)?, output);
+        let (route_sender, route_receiver) = mpsc::channel::<(Payload, Delivery)>(channel_bound);
+
+        routes.push(Route::new(output, route_sender));
+        output_handles.push(spawn_output(route_receiver, sender));
+    }
 
     let handle = tokio::spawn(async move {
+        let mut sequences = SourceSequences::default();
+        let mut unrouted_sources = HashSet::new();
+
         while let Some(payload) = payload_receiver.recv().await {
+            let payload_id = payload.id;
+            let mut offsets = payload.offsets();
+            sequences.assign(&mut offsets);
+
+            for source in &payload.sources {
+                let routed = routes.iter().any(|route| route.matches(&source.source_name));
+                if !routed && unrouted_sources.insert(source.source_name.clone()) {
+                    warn!("[{}] source matches no output, its logs are not sent", source.source_name);
+                }
+            }
+
+            let routed = router::split_payload(&routes, payload);
+            let deliveries = Delivery::track(payload_id, offsets, routed.len(), Some(report_sender.clone()));
+            dispatch(&routes, routed, deliveries).await;
+        }
+
+        // closing the routes lets every output finish its queued payloads
+        drop(routes);
+        for output_handle in output_handles {
+            let _ = output_handle.await;
+        }
+    });
+
+    Ok(handle)
+}
+
+// outputs with room get the payload before the router waits for a full one
+async fn dispatch(routes: &[Route], routed: Vec<(usize, Payload)>, deliveries: Vec<Delivery>) {
+    let mut full = Vec::new();
+
+    for ((i, payload), delivery) in routed.into_iter().zip(deliveries) {
+        let route = &routes[i];
+        trace!("[{}] routed {} sources", route.name(), payload.sources.len());
+
+        match route.try_send(payload, delivery) {
+            Ok(()) => {}
+            Err(TrySendError::Full((payload, delivery))) => match route.overflow() {
+                OverflowPolicy::Block => full.push((route, payload, delivery)),
+                OverflowPolicy::Drop => {
+                    warn!("[{}] output queue is full, payload {} dropped", route.name(), payload.id);
+                    fail(route, delivery);
+                }
+            },
+            Err(TrySendError::Closed((_, delivery))) => {
+                warn!("[{}] output channel closed", route.name());
+                fail(route, delivery);
+            }
+        }
+    }
+
+    for (route, payload, delivery) in full {
+        if let Err(mpsc::error::SendError((_, delivery))) = route.send(payload, delivery).await {
+            warn!("[{}] output channel closed", route.name());
+            fail(route, delivery);
+        }
+    }
+}
+
+fn fail(route: &Route, delivery: Delivery) {
+    delivery.resolve(DeliveryOutcome::FailedPermanently);
+    metrics().output(route.name()).failed();
+}
+
+// each output owns its own concurrency limit, a slow destination only stalls the others once
+// its queue is full and its overflow policy is `block`
+fn spawn_output(
+    mut payload_receiver: Receiver<(Payload, Delivery)>,
+    sender: Arc<dyn Sender>,
+) -> task::JoinHandle<()> {
+    let max_send_task = global_config().max_send_task as u32;
+    let semaphore = Arc::new(Semaphore::new(max_send_task as usize));
+
+    tokio::spawn(async move {
+        while let Some((payload, delivery)) = payload_receiver.recv().await {
             let permit = match semaphore.clone().acquire_owned().await {
                 Ok(permit) => permit,
                 Err(_) => break,
@@ -32,10 +163,53 @@ pub fn spawn_sender(mut payload_receiver: Receiver<Payload>) -> Result<task::Joi
             let sender = sender.clone();
             tokio::spawn(async move {
                 let _permit = permit;
-                let _ = sender.send(payload).await;
+                sender.send(payload, delivery).await;
             });
         }
-    });
 
-    Ok(handle)
+        // sends still in flight may spool their payload
+        let _ = semaphore.acquire_many(max_send_task).await;
+        sender.shutdown().await;
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SendType;
+    use crate::sender::payload::Source;
+
+    fn route(name: &str, overflow: OverflowPolicy) -> (Route, Receiver<(Payload, Delivery)>) {
+        let mut output = OutputConfig::new(name.to_string(), "http://localhost".to_string(), SendType::HTTP);
+        output.overflow = overflow;
+        let (route_sender, route_receiver) = mpsc::channel(1);
+
+        (Route::new(&output, route_sender), route_receiver)
+    }
+
+    fn payload() -> Payload {
+        Payload::with_agent_name("agent".to_string(), vec![Source::new("app1".to_string(), None, Vec::new())])
+    }
+
+    #[tokio::test]
+    async fn full_output_drops_without_stalling_the_others() {
+        let (slow, mut slow_receiver) = route("slow", OverflowPolicy::Drop);
+        let (fast, mut fast_receiver) = route("fast", OverflowPolicy::Block);
+        let routes = [slow, fast];
+        let (report_sender, mut report_receiver) = mpsc::unbounded_channel();
+
+        for payload_id in 1..=2 {
+            let deliveries = Delivery::track(payload_id, Vec::new(), 2, Some(report_sender.clone()));
+            dispatch(&routes, vec![(0, payload()), (1, payload())], deliveries).await;
+            let (_, delivery) = fast_receiver.recv().await.unwrap();
+            delivery.resolve(DeliveryOutcome::Delivered);
+        }
+
+        // the slow output still holds the first payload, the second was dropped for it
+        assert!(slow_receiver.try_recv().is_ok());
+        assert!(slow_receiver.try_recv().is_err());
+
+        let report = report_receiver.try_recv().unwrap();
+        assert_eq!((report.payload_id, report.outcome), (2, DeliveryOutcome::FailedPermanently));
+    }
 }
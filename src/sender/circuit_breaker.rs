@@ -0,0 +1,530 @@
+use async_trait::async_trait;
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::config::{CircuitBreakerConfig, OutputConfig};
+use crate::health::{health, OutputHealth};
+use crate::metrics::{metrics, OutputMetrics};
+
+use super::delivery::{Delivery, DeliveryOutcome};
+use super::payload::Payload;
+use super::spool::Spool;
+use super::{RetryGuard, SendResult, Sender};
+
+// how often a spool is checked for an open period that ended
+const PROBE_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+impl Display for CircuitState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CircuitState::Closed => write!(f, "closed"),
+            CircuitState::Open => write!(f, "open"),
+            CircuitState::HalfOpen => write!(f, "half-open"),
+        }
+    }
+}
+
+struct BreakerInner {
+    state: CircuitState,
+    consecutive_failures: u32,
+    opened_at: Instant,
+    probes_in_flight: u32,
+    probe_successes: u32,
+}
+
+pub struct CircuitBreaker {
+    name: Arc<str>,
+    failure_threshold: u32,
+    open_duration: Duration,
+    half_open_probes: u32,
+    inner: Mutex<BreakerInner>,
+    health: Arc<OutputHealth>,
+}
+
+impl CircuitBreaker {
+    pub fn new(name: Arc<str>, config: &CircuitBreakerConfig) -> Self {
+        Self {
+            health: health().output(&name),
+            name,
+            failure_threshold: config.failure_threshold,
+            open_duration: Duration::from_secs(config.open_secs),
+            half_open_probes: config.half_open_probes,
+            inner: Mutex::new(BreakerInner {
+                state: CircuitState::Closed,
+                consecutive_failures: 0,
+                opened_at: Instant::now(),
+                probes_in_flight: 0,
+                probe_successes: 0,
+            }),
+        }
+    }
+
+    pub fn state(&self) -> CircuitState {
+        self.lock().state
+    }
+
+    // whether a send may go to the network now, moves open to half-open once the open duration elapsed
+    pub fn try_acquire(&self) -> bool {
+        let mut inner = self.lock();
+
+        if inner.state == CircuitState::Open && inner.opened_at.elapsed() >= self.open_duration {
+            self.transition(&mut inner, CircuitState::HalfOpen);
+        }
+
+        match inner.state {
+            CircuitState::Closed => true,
+            CircuitState::Open => false,
+            CircuitState::HalfOpen => {
+                if inner.probes_in_flight < self.half_open_probes {
+                    inner.probes_in_flight += 1;
+                    true
+                } else {
+                    false
+                }
+            }
+        }
+    }
+
+    // returns true when this success closed the circuit
+    pub fn on_success(&self) -> bool {
+        let mut inner = self.lock();
+        inner.consecutive_failures = 0;
+
+        if inner.state != CircuitState::HalfOpen {
+            return false;
+        }
+
+        inner.probes_in_flight = inner.probes_in_flight.saturating_sub(1);
+        inner.probe_successes += 1;
+        if inner.probe_successes < self.half_open_probes {
+            return false;
+        }
+
+        self.transition(&mut inner, CircuitState::Closed);
+        true
+    }
+
+    pub fn on_failure(&self) {
+        let mut inner = self.lock();
+
+        match inner.state {
+            CircuitState::Closed => {
+                inner.consecutive_failures += 1;
+                if inner.consecutive_failures >= self.failure_threshold {
+                    self.transition(&mut inner, CircuitState::Open);
+                }
+            }
+            CircuitState::HalfOpen => self.transition(&mut inner, CircuitState::Open),
+            CircuitState::Open => {}
+        }
+    }
+
+    fn transition(&self, inner: &mut BreakerInner, to: CircuitState) {
+        let from = inner.state;
+        let consecutive_failures = inner.consecutive_failures;
+
+        inner.state = to;
+        self.health.set_circuit_open(to == CircuitState::Open);
+        inner.consecutive_failures = 0;
+        inner.probes_in_flight = 0;
+        inner.probe_successes = 0;
+        if to == CircuitState::Open {
+            inner.opened_at = Instant::now();
+        }
+
+        match to {
+            CircuitState::Open => warn!(
+                output = %self.name,
+                from = %from,
+                to = %to,
+                consecutive_failures,
+                open_secs = self.open_duration.as_secs(),
+                "circuit breaker state changed"
+            ),
+            _ => info!(output = %self.name, from = %from, to = %to, "circuit breaker state changed"),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BreakerInner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+// wraps a strategy so payloads are spooled instead of sent while the circuit is open
+pub struct CircuitBreakerSender {
+    name: Arc<str>,
+    inner: Arc<dyn Sender>,
+    breaker: CircuitBreaker,
+    spool: Spool<(Payload, Delivery)>,
+    flushing: AtomicBool,
+    metrics: Arc<OutputMetrics>,
+}
+
+impl CircuitBreakerSender {
+    pub fn wrap(inner: Arc<dyn Sender>, output: &OutputConfig) -> Arc<dyn Sender> {
+        if !output.circuit_breaker.enabled {
+            return inner;
+        }
+
+        let name: Arc<str> = Arc::from(output.name.as_str());
+        health().output(&name).set_spool_capacity(output.max_spool_size);
+        let sender = Arc::new(Self {
+            breaker: CircuitBreaker::new(name.clone(), &output.circuit_breaker),
+            name,
+            inner,
+            spool: Spool::new(output.max_spool_size),
+            flushing: AtomicBool::new(false),
+            metrics: metrics().output(&output.name),
+        });
+
+        sender.inner.guard_retries(Arc::downgrade(&sender) as Weak<Self>);
+        Self::spawn_probe_timer(Arc::downgrade(&sender));
+        sender
+    }
+
+    // spooled payloads are sent once the open period ends even when no new payload arrives,
+    // stops with the sender
+    fn spawn_probe_timer(sender: Weak<Self>) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(PROBE_INTERVAL);
+
+            loop {
+                ticker.tick().await;
+                let Some(sender) = sender.upgrade() else { break };
+                sender.probe_spool().await;
+            }
+        });
+    }
+
+    // the oldest spooled payload is the probe, a delivered probe closes the circuit and flushes the rest
+    async fn probe_spool(&self) {
+        if self.breaker.state() == CircuitState::Closed {
+            if self.spool.len() > 0 {
+                self.flush_spool().await;
+            }
+            return;
+        }
+
+        let Some((payload, delivery)) = self.spool.pop() else { return };
+        if !self.breaker.try_acquire() {
+            self.spool.push_front((payload, delivery));
+            return;
+        }
+        self.metrics.spool_size.store(self.spool.len() as u64, Ordering::Relaxed);
+
+        let result = self.inner.send(payload, delivery).await;
+        if self.record(&result) {
+            self.flush_spool().await;
+        }
+    }
+
+    fn spool(&self, payload: Payload, delivery: Delivery) -> SendResult {
+        delivery.resolve(DeliveryOutcome::Spooled);
+
+        if let Some((dropped, dropped_delivery)) = self.spool.push((payload, delivery)) {
+            warn!("[{}] spool is full, dropped oldest payload {}", self.name, dropped.id);
+            dropped_delivery.resolve(DeliveryOutcome::FailedPermanently);
+            self.metrics.failed();
+        }
+        self.metrics.spool_size.store(self.spool.len() as u64, Ordering::Relaxed);
+
+        SendResult::Spooled
+    }
+
+    // a failed probe goes back in front so the spool keeps its order
+    fn requeue(&self, payload: Payload, delivery: Delivery) {
+        delivery.resolve(DeliveryOutcome::Spooled);
+        self.spool.push_front((payload, delivery));
+        self.metrics.spool_size.store(self.spool.len() as u64, Ordering::Relaxed);
+    }
+
+    // record the network result, returns true when the circuit just closed
+    fn record(&self, result: &SendResult) -> bool {
+        match result {
+            // rejected payloads still prove the server is reachable
+            SendResult::Delivered | SendResult::Rejected => self.breaker.on_success(),
+            SendResult::Unreachable => {
+                self.breaker.on_failure();
+                false
+            }
+            SendResult::Spooled => false,
+        }
+    }
+
+    // drain spooled payloads in order while the circuit stays closed
+    async fn flush_spool(&self) {
+        if self.flushing.swap(true, Ordering::AcqRel) {
+            return;
+        }
+
+        let mut flushed = 0;
+        while self.breaker.state() == CircuitState::Closed {
+            let Some((payload, delivery)) = self.spool.pop() else { break };
+            self.metrics.spool_size.store(self.spool.len() as u64, Ordering::Relaxed);
+
+            let result = self.inner.send(payload, delivery).await;
+            self.record(&result);
+            flushed += 1;
+        }
+
+        self.flushing.store(false, Ordering::Release);
+        info!("[{}] flushed {flushed} spooled payloads ({} remaining)", self.name, self.spool.len());
+    }
+}
+
+#[async_trait]
+impl Sender for CircuitBreakerSender {
+    async fn send(&self, payload: Payload, delivery: Delivery) -> SendResult {
+        if !self.breaker.try_acquire() {
+            return self.spool(payload, delivery);
+        }
+
+        let result = self.inner.send(payload, delivery).await;
+        if self.record(&result) {
+            self.flush_spool().await;
+        }
+
+        result
+    }
+
+    // one last attempt at the spool, what is left is failed instead of silently lost
+    async fn shutdown(&self) {
+        self.probe_spool().await;
+
+        let mut dropped = 0;
+        while let Some((_, delivery)) = self.spool.pop() {
+            delivery.resolve(DeliveryOutcome::FailedPermanently);
+            self.metrics.failed();
+            dropped += 1;
+        }
+        self.metrics.spool_size.store(0, Ordering::Relaxed);
+
+        if dropped > 0 {
+            warn!("[{}] {dropped} spooled payloads were not sent before shutdown", self.name);
+        }
+    }
+}
+
+// retries of the inner strategy only reach the network while the circuit is closed
+impl RetryGuard for CircuitBreakerSender {
+    fn hold(&self, payload: Payload, delivery: Delivery) -> Option<(Payload, Delivery)> {
+        match self.breaker.state() {
+            CircuitState::Closed => Some((payload, delivery)),
+            CircuitState::HalfOpen => {
+                self.requeue(payload, delivery);
+                None
+            }
+            CircuitState::Open => {
+                self.spool(payload, delivery);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    fn breaker(open_secs: u64, half_open_probes: u32) -> CircuitBreaker {
+        let config = CircuitBreakerConfig {
+            enabled: true,
+            failure_threshold: 2,
+            open_secs,
+            half_open_probes,
+        };
+
+        CircuitBreaker::new(Arc::from("test"), &config)
+    }
+
+    #[test]
+    fn opens_after_consecutive_failures() {
+        let breaker = breaker(30, 1);
+
+        breaker.on_failure();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        breaker.on_success();
+        breaker.on_failure();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        breaker.on_failure();
+
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(!breaker.try_acquire());
+    }
+
+    #[test]
+    fn half_open_allows_limited_probes() {
+        let breaker = breaker(0, 1);
+        breaker.on_failure();
+        breaker.on_failure();
+
+        assert!(breaker.try_acquire());
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        assert!(!breaker.try_acquire());
+    }
+
+    #[test]
+    fn probe_success_closes() {
+        let breaker = breaker(0, 1);
+        breaker.on_failure();
+        breaker.on_failure();
+
+        assert!(breaker.try_acquire());
+        assert!(breaker.on_success());
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[test]
+    fn probe_failure_reopens() {
+        let breaker = breaker(30, 1);
+        breaker.on_failure();
+        breaker.on_failure();
+        breaker.inner.lock().unwrap().opened_at -= Duration::from_secs(30);
+
+        assert!(breaker.try_acquire());
+        breaker.on_failure();
+
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(!breaker.try_acquire());
+    }
+
+    struct ScriptedSender {
+        results: Mutex<VecDeque<SendResult>>,
+        sent: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl Sender for ScriptedSender {
+        async fn send(&self, payload: Payload, _delivery: Delivery) -> SendResult {
+            self.sent.lock().unwrap().push(payload.agent_name);
+            self.results.lock().unwrap().pop_front().unwrap_or(SendResult::Delivered)
+        }
+    }
+
+    fn payload(agent_name: &str) -> Payload {
+        Payload::with_agent_name(agent_name.to_string(), Vec::new())
+    }
+
+    fn delivery() -> Delivery {
+        Delivery::track(1, Vec::new(), 1, None).remove(0)
+    }
+
+    #[tokio::test]
+    async fn open_circuit_spools_and_flushes_on_close() {
+        let inner = Arc::new(ScriptedSender {
+            results: Mutex::new(VecDeque::from([SendResult::Unreachable, SendResult::Unreachable])),
+            sent: Mutex::new(Vec::new()),
+        });
+
+        let sender = CircuitBreakerSender {
+            name: Arc::from("test"),
+            inner: inner.clone(),
+            breaker: breaker(30, 1),
+            spool: Spool::new(10),
+            flushing: AtomicBool::new(false),
+            metrics: Arc::default(),
+        };
+
+        assert_eq!(sender.send(payload("1"), delivery()).await, SendResult::Unreachable);
+        assert_eq!(sender.send(payload("2"), delivery()).await, SendResult::Unreachable);
+        assert_eq!(sender.breaker.state(), CircuitState::Open);
+
+        assert_eq!(sender.send(payload("3"), delivery()).await, SendResult::Spooled);
+        assert_eq!(sender.spool.len(), 1);
+
+        sender.breaker.inner.lock().unwrap().opened_at -= Duration::from_secs(30);
+        assert_eq!(sender.send(payload("4"), delivery()).await, SendResult::Delivered);
+
+        assert_eq!(sender.breaker.state(), CircuitState::Closed);
+        assert_eq!(sender.spool.len(), 0);
+        assert_eq!(*inner.sent.lock().unwrap(), vec!["1", "2", "4", "3"]);
+    }
+
+    #[test]
+    fn retries_are_spooled_while_the_circuit_is_not_closed() {
+        let inner = Arc::new(ScriptedSender { results: Mutex::default(), sent: Mutex::default() });
+        let sender = CircuitBreakerSender {
+            name: Arc::from("test"),
+            inner: inner.clone(),
+            breaker: breaker(30, 1),
+            spool: Spool::new(10),
+            flushing: AtomicBool::new(false),
+            metrics: Arc::default(),
+        };
+
+        assert!(sender.hold(payload("1"), delivery()).is_some());
+
+        sender.breaker.on_failure();
+        sender.breaker.on_failure();
+        sender.spool(payload("2"), delivery());
+        assert!(sender.hold(payload("3"), delivery()).is_none());
+
+        // a probe that failed and is retried stays the oldest spooled payload
+        sender.breaker.inner.lock().unwrap().opened_at -= Duration::from_secs(30);
+        assert!(sender.breaker.try_acquire());
+        assert!(sender.hold(payload("4"), delivery()).is_none());
+
+        let spooled: Vec<_> = std::iter::from_fn(|| sender.spool.pop()).map(|(payload, _)| payload.agent_name).collect();
+        assert_eq!(spooled, vec!["4", "2", "3"]);
+        assert!(inner.sent.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn spool_is_probed_without_new_payloads_and_failed_on_shutdown() {
+        let inner = Arc::new(ScriptedSender {
+            results: Mutex::new(VecDeque::from([SendResult::Unreachable, SendResult::Unreachable, SendResult::Unreachable])),
+            sent: Mutex::new(Vec::new()),
+        });
+
+        let sender = CircuitBreakerSender {
+            name: Arc::from("test"),
+            inner: inner.clone(),
+            breaker: breaker(30, 1),
+            spool: Spool::new(10),
+            flushing: AtomicBool::new(false),
+            metrics: Arc::default(),
+        };
+
+        sender.send(payload("1"), delivery()).await;
+        sender.send(payload("2"), delivery()).await;
+        sender.send(payload("3"), delivery()).await;
+        sender.send(payload("4"), delivery()).await;
+        assert_eq!(sender.spool.len(), 2);
+
+        // still open, nothing is sent
+        sender.probe_spool().await;
+        assert_eq!(sender.spool.len(), 2);
+
+        // the probe fails and opens the circuit again
+        sender.breaker.inner.lock().unwrap().opened_at -= Duration::from_secs(30);
+        sender.probe_spool().await;
+        assert_eq!(sender.breaker.state(), CircuitState::Open);
+        assert_eq!(sender.spool.len(), 1);
+
+        sender.breaker.inner.lock().unwrap().opened_at -= Duration::from_secs(30);
+        sender.probe_spool().await;
+        assert_eq!(sender.breaker.state(), CircuitState::Closed);
+        assert_eq!(*inner.sent.lock().unwrap(), vec!["1", "2", "3", "4"]);
+
+        let (report_sender, mut reports) = tokio::sync::mpsc::unbounded_channel();
+        sender.breaker.on_failure();
+        sender.breaker.on_failure();
+        sender.send(payload("5"), Delivery::track(5, Vec::new(), 1, Some(report_sender)).remove(0)).await;
+        assert_eq!(reports.try_recv().unwrap().outcome, DeliveryOutcome::Spooled);
+
+        sender.shutdown().await;
+        assert_eq!(sender.spool.len(), 0);
+        assert_eq!(reports.try_recv().unwrap().outcome, DeliveryOutcome::FailedPermanently);
+    }
+}
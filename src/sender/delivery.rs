@@ -0,0 +1,371 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task;
+use tracing::{debug, error, warn};
+
+use super::payload::SourceOffset;
+use crate::checkpoint::{Checkpoint, CheckpointStore};
+
+const CHECKPOINT_SAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeliveryOutcome {
+    Delivered,
+    // rejected by the server, retries exhausted or dropped from a full spool
+    FailedPermanently,
+    // buffered in memory, a later report follows once it is flushed
+    Spooled,
+}
+
+#[derive(Debug)]
+pub struct DeliveryReport {
+    pub payload_id: u64,
+    pub offsets: Vec<SourceOffset>,
+    pub outcome: DeliveryOutcome,
+}
+
+struct TrackerState {
+    outcomes: Vec<Option<DeliveryOutcome>>,
+    reported: Option<DeliveryOutcome>,
+}
+
+// combines the outcome of every output a payload was routed to
+struct DeliveryTracker {
+    payload_id: u64,
+    offsets: Vec<SourceOffset>,
+    state: Mutex<TrackerState>,
+    report_sender: Option<mpsc::UnboundedSender<DeliveryReport>>,
+}
+
+impl DeliveryTracker {
+    fn resolve(&self, index: usize, outcome: DeliveryOutcome) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.outcomes[index] = Some(outcome);
+
+        let Some(combined) = Self::combine(&state.outcomes) else { return };
+        if state.reported == Some(combined) {
+            return;
+        }
+        state.reported = Some(combined);
+
+        if let Some(report_sender) = &self.report_sender {
+            let _ = report_sender.send(DeliveryReport {
+                payload_id: self.payload_id,
+                offsets: self.offsets.clone(),
+                outcome: combined,
+            });
+        }
+    }
+
+    // delivered only when every output delivered it
+    fn combine(outcomes: &[Option<DeliveryOutcome>]) -> Option<DeliveryOutcome> {
+        let mut combined = DeliveryOutcome::Delivered;
+
+        for outcome in outcomes {
+            match outcome {
+                None => return None,
+                Some(DeliveryOutcome::FailedPermanently) => combined = DeliveryOutcome::FailedPermanently,
+                Some(DeliveryOutcome::Spooled) if combined == DeliveryOutcome::Delivered => combined = DeliveryOutcome::Spooled,
+                Some(_) => {}
+            }
+        }
+
+        Some(combined)
+    }
+}
+
+// handle given to a sender together with the payload, senders must resolve it once the
+// payload is delivered, failed for good or spooled. resolving again later updates the report.
+#[derive(Clone)]
+pub struct Delivery {
+    tracker: Arc<DeliveryTracker>,
+    index: usize,
+}
+
+impl Delivery {
+    pub fn track(
+        payload_id: u64,
+        offsets: Vec<SourceOffset>,
+        output_count: usize,
+        report_sender: Option<mpsc::UnboundedSender<DeliveryReport>>,
+    ) -> Vec<Delivery> {
+        // nothing routed, there is nothing left to wait for
+        if output_count == 0 {
+            if let Some(report_sender) = report_sender {
+                let _ = report_sender.send(DeliveryReport {
+                    payload_id,
+                    offsets,
+                    outcome: DeliveryOutcome::Delivered,
+                });
+            }
+            return Vec::new();
+        }
+
+        let tracker = Arc::new(DeliveryTracker {
+            payload_id,
+            offsets,
+            state: Mutex::new(TrackerState {
+                outcomes: vec![None; output_count],
+                reported: None,
+            }),
+            report_sender,
+        });
+
+        (0..output_count)
+            .map(|index| Delivery { tracker: tracker.clone(), index })
+            .collect()
+    }
+
+    pub fn resolve(&self, outcome: DeliveryOutcome) {
+        self.tracker.resolve(self.index, outcome);
+    }
+}
+
+// numbers the payloads of every source in the order they are sent
+#[derive(Default)]
+pub struct SourceSequences {
+    next: HashMap<String, u64>,
+}
+
+impl SourceSequences {
+    // entries of the same source in one payload, like lines before and after a rotation, share a number
+    pub fn assign(&mut self, offsets: &mut [SourceOffset]) {
+        let mut assigned: HashMap<String, u64> = HashMap::new();
+
+        for offset in offsets {
+            let seq = *assigned.entry(offset.source_name.clone()).or_insert_with(|| {
+                let next = self.next.entry(offset.source_name.clone()).or_insert(0);
+                *next += 1;
+                *next - 1
+            });
+            offset.seq = seq;
+        }
+    }
+}
+
+#[derive(Default)]
+struct PendingPayloads {
+    // sequence of the oldest payload that is not resolved yet
+    next_seq: u64,
+    resolved: BTreeMap<u64, (DeliveryOutcome, Vec<SourceOffset>)>,
+}
+
+// payloads of a source resolve out of order when older ones are retried or spooled, offsets are
+// only committed once every earlier payload of the source resolved so a crash never skips lines
+pub struct OffsetCommitter {
+    // a failed payload counts as resolved, otherwise it stops the source from moving forward
+    skip_failed: bool,
+    sources: HashMap<String, PendingPayloads>,
+}
+
+impl OffsetCommitter {
+    pub fn new(skip_failed: bool) -> Self {
+        Self {
+            skip_failed,
+            sources: HashMap::new(),
+        }
+    }
+
+    // the offsets that became safe to commit with this report, spooled payloads are still pending
+    pub fn resolve(&mut self, report: &DeliveryReport) -> Vec<SourceOffset> {
+        if report.outcome == DeliveryOutcome::Spooled {
+            return Vec::new();
+        }
+
+        let mut committable = Vec::new();
+        let mut by_source: HashMap<&str, Vec<SourceOffset>> = HashMap::new();
+        for offset in &report.offsets {
+            by_source.entry(&offset.source_name).or_default().push(offset.clone());
+        }
+
+        for (source_name, offsets) in by_source {
+            let seq = offsets[0].seq;
+            let pending = self.sources.entry(source_name.to_string()).or_default();
+            if seq < pending.next_seq {
+                continue;
+            }
+            pending.resolved.entry(seq).or_insert((report.outcome, offsets));
+
+            while let Some(entry) = pending.resolved.first_entry() {
+                if *entry.key() != pending.next_seq {
+                    break;
+                }
+                if entry.get().0 == DeliveryOutcome::FailedPermanently && !self.skip_failed {
+                    break;
+                }
+
+                let (outcome, offsets) = entry.remove();
+                pending.next_seq += 1;
+                if outcome == DeliveryOutcome::Delivered {
+                    committable.extend(offsets);
+                }
+            }
+        }
+
+        committable
+    }
+}
+
+// logs every report and moves checkpoints forward once offsets are delivered
+pub fn spawn_delivery_logger(
+    mut report_receiver: mpsc::UnboundedReceiver<DeliveryReport>,
+    checkpoints: Option<Arc<CheckpointStore>>,
+) -> task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(CHECKPOINT_SAVE_INTERVAL);
+        // lines of a failed payload are lost already, they do not hold back the checkpoint
+        let mut committer = OffsetCommitter::new(true);
+
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => save_checkpoints(checkpoints.as_deref()),
+                report = report_receiver.recv() => {
+                    let Some(report) = report else { break };
+
+                    match report.outcome {
+                        DeliveryOutcome::Delivered => debug!("payload {} delivered: {:?}", report.payload_id, report.offsets),
+                        DeliveryOutcome::Spooled => warn!("payload {} spooled: {:?}", report.payload_id, report.offsets),
+                        DeliveryOutcome::FailedPermanently => error!("payload {} failed permanently: {:?}", report.payload_id, report.offsets),
+                    }
+
+                    if let Some(checkpoints) = &checkpoints {
+                        record_checkpoints(checkpoints, &committer.resolve(&report));
+                    }
+                }
+            }
+        }
+
+        save_checkpoints(checkpoints.as_deref());
+    })
+}
+
+fn record_checkpoints(checkpoints: &CheckpointStore, offsets: &[SourceOffset]) {
+    for offset in offsets {
+        if let Some(inode) = offset.inode {
+            let checkpoint = Checkpoint { inode, offset: offset.offset, cursor: offset.cursor.clone() };
+            checkpoints.record(&offset.source_name, checkpoint);
+        }
+    }
+}
+
+fn save_checkpoints(checkpoints: Option<&CheckpointStore>) {
+    if let Some(Err(e)) = checkpoints.map(CheckpointStore::save) {
+        error!("failed to save checkpoints: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(output_count: usize) -> (Vec<Delivery>, mpsc::UnboundedReceiver<DeliveryReport>) {
+        let (report_sender, report_receiver) = mpsc::unbounded_channel();
+
+        (Delivery::track(1, Vec::new(), output_count, Some(report_sender)), report_receiver)
+    }
+
+    #[test]
+    fn reports_once_every_output_resolved() {
+        let (deliveries, mut reports) = track(2);
+
+        deliveries[0].resolve(DeliveryOutcome::Delivered);
+        assert!(reports.try_recv().is_err());
+
+        deliveries[1].resolve(DeliveryOutcome::Delivered);
+        assert_eq!(reports.try_recv().unwrap().outcome, DeliveryOutcome::Delivered);
+    }
+
+    #[test]
+    fn any_permanent_failure_fails_payload() {
+        let (deliveries, mut reports) = track(2);
+
+        deliveries[0].resolve(DeliveryOutcome::FailedPermanently);
+        deliveries[1].resolve(DeliveryOutcome::Spooled);
+
+        assert_eq!(reports.try_recv().unwrap().outcome, DeliveryOutcome::FailedPermanently);
+    }
+
+    #[test]
+    fn spooled_is_reported_again_when_delivered() {
+        let (deliveries, mut reports) = track(2);
+
+        deliveries[0].resolve(DeliveryOutcome::Delivered);
+        deliveries[1].resolve(DeliveryOutcome::Spooled);
+        assert_eq!(reports.try_recv().unwrap().outcome, DeliveryOutcome::Spooled);
+
+        deliveries[1].resolve(DeliveryOutcome::Delivered);
+        assert_eq!(reports.try_recv().unwrap().outcome, DeliveryOutcome::Delivered);
+        assert!(reports.try_recv().is_err());
+    }
+
+    fn report(seq: u64, offset: u64, outcome: DeliveryOutcome) -> DeliveryReport {
+        let offsets = vec![SourceOffset {
+            source_name: "app1".to_string(),
+            inode: Some(1),
+            offset,
+            cursor: None,
+            seq,
+        }];
+
+        DeliveryReport { payload_id: seq, offsets, outcome }
+    }
+
+    fn committed(committer: &mut OffsetCommitter, report: DeliveryReport) -> Vec<u64> {
+        committer.resolve(&report).iter().map(|offset| offset.offset).collect()
+    }
+
+    #[test]
+    fn commits_only_behind_every_earlier_payload() {
+        let mut committer = OffsetCommitter::new(true);
+
+        // payload 0 is retried while 1 and 2 are delivered
+        assert!(committed(&mut committer, report(1, 20, DeliveryOutcome::Delivered)).is_empty());
+        assert!(committed(&mut committer, report(0, 10, DeliveryOutcome::Spooled)).is_empty());
+        assert!(committed(&mut committer, report(2, 30, DeliveryOutcome::Delivered)).is_empty());
+
+        assert_eq!(committed(&mut committer, report(0, 10, DeliveryOutcome::Delivered)), [10, 20, 30]);
+
+        // a failed payload is skipped in the agent
+        assert!(committed(&mut committer, report(4, 50, DeliveryOutcome::Delivered)).is_empty());
+        assert_eq!(committed(&mut committer, report(3, 40, DeliveryOutcome::FailedPermanently)), [50]);
+    }
+
+    #[test]
+    fn failed_payload_stops_the_source_unless_skipped() {
+        let mut committer = OffsetCommitter::new(false);
+
+        assert_eq!(committed(&mut committer, report(0, 10, DeliveryOutcome::Delivered)), [10]);
+        assert!(committed(&mut committer, report(1, 20, DeliveryOutcome::FailedPermanently)).is_empty());
+        assert!(committed(&mut committer, report(2, 30, DeliveryOutcome::Delivered)).is_empty());
+    }
+
+    #[test]
+    fn sequences_per_source() {
+        let offset = |source_name: &str| SourceOffset {
+            source_name: source_name.to_string(),
+            inode: Some(1),
+            offset: 1,
+            cursor: None,
+            seq: 0,
+        };
+        let mut sequences = SourceSequences::default();
+
+        let mut first = vec![offset("app1"), offset("app2"), offset("app1")];
+        sequences.assign(&mut first);
+        let mut second = vec![offset("app1")];
+        sequences.assign(&mut second);
+
+        assert_eq!(first.iter().map(|offset| offset.seq).collect::<Vec<_>>(), [0, 0, 0]);
+        assert_eq!(second[0].seq, 1);
+    }
+
+    #[test]
+    fn unrouted_payload_is_delivered() {
+        let (deliveries, mut reports) = track(0);
+
+        assert!(deliveries.is_empty());
+        assert_eq!(reports.try_recv().unwrap().outcome, DeliveryOutcome::Delivered);
+    }
+}
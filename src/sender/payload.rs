@@ -1,42 +1,159 @@
 use chrono::{DateTime, Utc};
 use serde::Serialize;
+use std::{
+    process,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, OnceLock,
+    },
+};
 
-use crate::{config::global_config, log_event::LogEvent};
+use crate::{
+    config::{global_config, Labels},
+    log_event::{LogEvent, SourceMeta},
+    parser::Fields,
+};
+
+static NEXT_PAYLOAD_ID: AtomicU64 = AtomicU64::new(1);
+static AGENT_META: OnceLock<Arc<AgentMeta>> = OnceLock::new();
 
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Payload {
+    // identifies the payload in delivery reports, split payloads keep the id of the original
+    #[serde(skip)]
+    pub id: u64,
     pub agent_name: String,
+    #[serde(flatten)]
+    pub agent: Arc<AgentMeta>,
     pub sources: Vec<Source>,
 }
 
+// describes the machine the agent runs on, the same for every payload
+#[derive(Debug, Default, Serialize)]
+pub struct AgentMeta {
+    pub hostname: String,
+    pub pid: u32,
+    #[serde(skip_serializing_if = "Labels::is_empty")]
+    pub labels: Labels,
+}
+
 impl Payload {
     pub fn new(sources: Vec<Source>) -> Self {
+        let agent = AGENT_META.get_or_init(|| Arc::new(AgentMeta::from_global())).clone();
+
         Self {
+            id: NEXT_PAYLOAD_ID.fetch_add(1, Ordering::Relaxed),
             agent_name: global_config().agent_name.clone(),
+            agent,
+            sources,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn with_agent_name(agent_name: String, sources: Vec<Source>) -> Self {
+        Self {
+            id: NEXT_PAYLOAD_ID.fetch_add(1, Ordering::Relaxed),
+            agent_name,
+            agent: Arc::default(),
             sources,
         }
     }
+
+    // the furthest offset of every source, safe to commit once the payload is delivered
+    pub fn offsets(&self) -> Vec<SourceOffset> {
+        self.sources
+            .iter()
+            .filter_map(|source| {
+                source.logs.iter().max_by_key(|logs| logs.offset).map(|logs| SourceOffset {
+                    source_name: source.source_name.clone(),
+                    inode: source.inode,
+                    offset: logs.offset,
+                    cursor: logs.cursor.clone(),
+                    seq: 0,
+                })
+            })
+            .collect()
+    }
+}
+
+impl AgentMeta {
+    fn from_global() -> Self {
+        let global = global_config();
+        let hostname = global
+            .hostname
+            .clone()
+            .unwrap_or_else(|| gethostname::gethostname().to_string_lossy().into_owned());
+
+        Self {
+            hostname,
+            pid: process::id(),
+            labels: global.labels.clone(),
+        }
+    }
 }
 
-#[derive(Serialize)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceOffset {
+    pub source_name: String,
+    // None for events the agent generated itself
+    pub inode: Option<u64>,
+    pub offset: u64,
+    pub cursor: Option<String>,
+    // position of the payload among the payloads of this source, set by the sender
+    pub seq: u64,
+}
+
+#[derive(Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Source {
     pub source_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inode: Option<u64>,
+    #[serde(skip_serializing_if = "Labels::is_empty")]
+    pub labels: Labels,
     pub logs: Vec<Logs>,
 }
 
 impl Source {
-    pub fn new(source_name: String, logs: Vec<Logs>) -> Self {
-        Self { source_name, logs }
+    pub fn new(source_name: String, meta: Option<&SourceMeta>, logs: Vec<Logs>) -> Self {
+        Self {
+            source_name,
+            file_path: meta.map(|meta| meta.path.clone()),
+            inode: meta.map(|meta| meta.inode),
+            labels: meta.map(|meta| meta.labels.clone()).unwrap_or_default(),
+            logs,
+        }
     }
 }
 
-#[derive(Serialize)]
+#[derive(Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Logs {
+    // empty when the source drops the original line after parsing
+    #[serde(skip_serializing_if = "String::is_empty")]
     pub data: String,
     pub timestamp: DateTime<Utc>,
+    pub observed_timestamp: DateTime<Utc>,
+
+    #[serde(skip_serializing_if = "Fields::is_empty")]
+    pub fields: Fields,
+
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub parse_error: bool,
+
+    // byte offset right after the line, the realtime in microseconds for journal entries
+    #[serde(skip_serializing_if = "is_zero")]
+    pub offset: u64,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub line_number: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+
+    #[serde(skip_serializing_if = "is_single")]
+    pub repeat_count: u64,
 }
 
 impl Logs {
@@ -44,6 +161,61 @@ impl Logs {
         Self {
             data: log_event.log,
             timestamp: log_event.timestamp,
+            observed_timestamp: log_event.observed_timestamp,
+            fields: log_event.fields,
+            parse_error: log_event.parse_error,
+            offset: log_event.offset,
+            line_number: log_event.line_number,
+            cursor: log_event.cursor,
+            repeat_count: log_event.repeat_count,
         }
     }
 }
+
+fn is_zero(value: &u64) -> bool {
+    *value == 0
+}
+
+fn is_single(value: &u64) -> bool {
+    *value <= 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn serializes_agent_and_source_meta() {
+        let meta = SourceMeta {
+            path: "/var/log/app1.log".to_string(),
+            inode: 42,
+            labels: Labels::from([("team".to_string(), "core".to_string())]),
+        };
+
+        let mut event = LogEvent::new("app1".to_string(), "hello".to_string(), 6);
+        event.line_number = 1;
+
+        let mut payload = Payload::with_agent_name(
+            "agent".to_string(),
+            vec![Source::new("app1".to_string(), Some(&meta), vec![Logs::from_event(event)])],
+        );
+        payload.agent = Arc::new(AgentMeta {
+            hostname: "web-1".to_string(),
+            pid: 7,
+            labels: Labels::from([("env".to_string(), "prod".to_string())]),
+        });
+
+        let value = serde_json::to_value(&payload).unwrap();
+        let logs = &value["sources"][0]["logs"][0];
+
+        assert_eq!(value["hostname"], "web-1");
+        assert_eq!(value["pid"], 7);
+        assert_eq!(value["labels"], json!({"env": "prod"}));
+        assert_eq!(value["sources"][0]["filePath"], "/var/log/app1.log");
+        assert_eq!(value["sources"][0]["inode"], 42);
+        assert_eq!(value["sources"][0]["labels"], json!({"team": "core"}));
+        assert_eq!(logs["offset"], 6);
+        assert_eq!(logs["lineNumber"], 1);
+    }
+}
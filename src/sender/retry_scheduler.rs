@@ -0,0 +1,237 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, Semaphore};
+use tokio::time::Instant;
+use tracing::error;
+
+struct Scheduled<T> {
+    due: Instant,
+    seq: u64,
+    item: T,
+}
+
+// BinaryHeap is a max-heap, reverse so the earliest due (then first scheduled) is on top
+impl<T> Ord for Scheduled<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.due.cmp(&self.due).then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+impl<T> PartialOrd for Scheduled<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> PartialEq for Scheduled<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.due == other.due && self.seq == other.seq
+    }
+}
+
+impl<T> Eq for Scheduled<T> {}
+
+// time-ordered delay queue, fires items when their backoff elapsed and runs at most
+// max_concurrency handlers at once. a handler returns the item with the next delay to retry again.
+pub struct RetryScheduler<T> {
+    sender: mpsc::Sender<(T, Instant)>,
+}
+
+impl<T: Send + 'static> RetryScheduler<T> {
+    pub fn spawn<F, Fut>(channel_bound: usize, max_concurrency: usize, handler: F) -> Self
+    where
+        F: Fn(T) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Option<(T, Duration)>> + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel(channel_bound);
+        tokio::spawn(Self::run(receiver, sender.downgrade(), max_concurrency, handler));
+
+        Self { sender }
+    }
+
+    pub async fn schedule(&self, item: T, delay: Duration) -> Result<(), mpsc::error::SendError<T>> {
+        self.sender
+            .send((item, Instant::now() + delay))
+            .await
+            .map_err(|mpsc::error::SendError((item, _))| mpsc::error::SendError(item))
+    }
+
+    async fn run<F, Fut>(
+        mut receiver: mpsc::Receiver<(T, Instant)>,
+        requeue: mpsc::WeakSender<(T, Instant)>,
+        max_concurrency: usize,
+        handler: F,
+    ) where
+        F: Fn(T) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Option<(T, Duration)>> + Send + 'static,
+    {
+        let semaphore = Arc::new(Semaphore::new(max_concurrency));
+        let handler = Arc::new(handler);
+        let mut queue: BinaryHeap<Scheduled<T>> = BinaryHeap::new();
+        let mut seq = 0_u64;
+        let mut closed = false;
+
+        loop {
+            let next_due = queue.peek().map(|scheduled| scheduled.due);
+            let is_due = next_due.is_some_and(|due| due <= Instant::now());
+
+            tokio::select! {
+                received = receiver.recv(), if !closed => match received {
+                    Some((item, due)) => {
+                        queue.push(Scheduled { due, seq, item });
+                        seq += 1;
+                    }
+                    None => closed = true,
+                },
+                permit = semaphore.clone().acquire_owned(), if is_due => {
+                    let Ok(permit) = permit else { break };
+                    let Some(Scheduled { item, .. }) = queue.pop() else { continue };
+
+                    // in-flight handlers keep the channel open so they can schedule the next attempt
+                    let requeue = requeue.upgrade();
+                    let handler = handler.clone();
+                    tokio::spawn(async move {
+                        let next = handler(item).await;
+                        drop(permit);
+
+                        if let Some((item, delay)) = next {
+                            let scheduled = requeue.map(|requeue| async move {
+                                requeue.send((item, Instant::now() + delay)).await
+                            });
+                            let rescheduled = match scheduled {
+                                Some(scheduled) => scheduled.await.is_ok(),
+                                None => false,
+                            };
+
+                            if !rescheduled {
+                                error!("Retry scheduler is closed, dropped retry");
+                            }
+                        }
+                    });
+                }
+                _ = tokio::time::sleep_until(next_due.unwrap_or_else(Instant::now)), if next_due.is_some() && !is_due => {}
+                else => break,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+
+    #[tokio::test(start_paused = true)]
+    async fn fires_in_due_order() {
+        let (done_sender, mut done_receiver) = mpsc::unbounded_channel();
+        let scheduler = RetryScheduler::spawn(16, 1, move |item: u64| {
+            let done_sender = done_sender.clone();
+            async move {
+                let _ = done_sender.send((item, Instant::now()));
+                None
+            }
+        });
+
+        let started = Instant::now();
+        for delay in [30, 10, 20, 10] {
+            scheduler.schedule(delay, Duration::from_millis(delay)).await.unwrap();
+        }
+
+        let mut fired = Vec::new();
+        for _ in 0..4 {
+            let (item, at) = done_receiver.recv().await.unwrap();
+            assert!(at - started >= Duration::from_millis(item));
+            fired.push(item);
+        }
+
+        assert_eq!(fired, vec![10, 10, 20, 30]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn limits_concurrent_handlers() {
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+        let (done_sender, mut done_receiver) = mpsc::unbounded_channel();
+
+        let scheduler = {
+            let in_flight = in_flight.clone();
+            let max_in_flight = max_in_flight.clone();
+            RetryScheduler::spawn(16, 2, move |item: u32| {
+                let in_flight = in_flight.clone();
+                let max_in_flight = max_in_flight.clone();
+                let done_sender = done_sender.clone();
+                async move {
+                    let current = in_flight.fetch_add(1, AtomicOrdering::SeqCst) + 1;
+                    max_in_flight.fetch_max(current, AtomicOrdering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    in_flight.fetch_sub(1, AtomicOrdering::SeqCst);
+
+                    let _ = done_sender.send(item);
+                    None
+                }
+            })
+        };
+
+        for item in 0..6 {
+            scheduler.schedule(item, Duration::ZERO).await.unwrap();
+        }
+
+        let mut done = Vec::new();
+        for _ in 0..6 {
+            done.push(done_receiver.recv().await.unwrap());
+        }
+        done.sort();
+
+        assert_eq!(done, vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(max_in_flight.load(AtomicOrdering::SeqCst), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn slow_handler_does_not_stall_others() {
+        let (done_sender, mut done_receiver) = mpsc::unbounded_channel();
+        let scheduler = RetryScheduler::spawn(16, 2, move |item: &'static str| {
+            let done_sender = done_sender.clone();
+            async move {
+                if item == "slow" {
+                    tokio::time::sleep(Duration::from_secs(60)).await;
+                }
+                let _ = done_sender.send(item);
+                None
+            }
+        });
+
+        scheduler.schedule("slow", Duration::ZERO).await.unwrap();
+        scheduler.schedule("fast-1", Duration::from_millis(10)).await.unwrap();
+        scheduler.schedule("fast-2", Duration::from_millis(20)).await.unwrap();
+
+        assert_eq!(done_receiver.recv().await, Some("fast-1"));
+        assert_eq!(done_receiver.recv().await, Some("fast-2"));
+        assert_eq!(done_receiver.recv().await, Some("slow"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn handler_can_reschedule() {
+        let (done_sender, mut done_receiver) = mpsc::unbounded_channel();
+        let scheduler = RetryScheduler::spawn(16, 1, move |attempt: u8| {
+            let done_sender = done_sender.clone();
+            async move {
+                let _ = done_sender.send((attempt, Instant::now()));
+                (attempt < 3).then(|| (attempt + 1, Duration::from_millis(100)))
+            }
+        });
+
+        let started = Instant::now();
+        scheduler.schedule(1, Duration::ZERO).await.unwrap();
+
+        let mut attempts = Vec::new();
+        for _ in 0..3 {
+            let (attempt, at) = done_receiver.recv().await.unwrap();
+            attempts.push((attempt, (at - started).as_millis()));
+        }
+
+        assert_eq!(attempts, vec![(1, 0), (2, 100), (3, 200)]);
+    }
+}
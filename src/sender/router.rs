@@ -0,0 +1,165 @@
+use glob::Pattern;
+use tokio::sync::mpsc;
+
+use crate::config::{OutputConfig, OverflowPolicy};
+
+use super::delivery::Delivery;
+use super::payload::{Payload, Source};
+
+pub struct Route {
+    name: String,
+    patterns: Vec<Pattern>,
+    overflow: OverflowPolicy,
+    payload_sender: mpsc::Sender<(Payload, Delivery)>,
+}
+
+impl Route {
+    pub fn new(output: &OutputConfig, payload_sender: mpsc::Sender<(Payload, Delivery)>) -> Self {
+        // patterns are checked while loading config
+        let patterns = output
+            .sources
+            .iter()
+            .filter_map(|pattern| Pattern::new(pattern).ok())
+            .collect();
+
+        Self {
+            name: output.name.clone(),
+            patterns,
+            overflow: output.overflow,
+            payload_sender,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn overflow(&self) -> OverflowPolicy {
+        self.overflow
+    }
+
+    pub fn matches(&self, source_name: &str) -> bool {
+        self.patterns.is_empty() || self.patterns.iter().any(|p| p.matches(source_name))
+    }
+
+    pub fn try_send(&self, payload: Payload, delivery: Delivery) -> Result<(), mpsc::error::TrySendError<(Payload, Delivery)>> {
+        self.payload_sender.try_send((payload, delivery))
+    }
+
+    pub async fn send(&self, payload: Payload, delivery: Delivery) -> Result<(), mpsc::error::SendError<(Payload, Delivery)>> {
+        self.payload_sender.send((payload, delivery)).await
+    }
+}
+
+// split payload by source so every route only receives the sources it is interested in
+pub fn split_payload(routes: &[Route], payload: Payload) -> Vec<(usize, Payload)> {
+    let mut routed: Vec<Vec<Source>> = routes.iter().map(|_| Vec::new()).collect();
+
+    for source in payload.sources {
+        let targets: Vec<usize> = routes
+            .iter()
+            .enumerate()
+            .filter(|(_, route)| route.matches(&source.source_name))
+            .map(|(i, _)| i)
+            .collect();
+
+        if let Some((&last, rest)) = targets.split_last() {
+            for &i in rest {
+                routed[i].push(source.clone());
+            }
+            routed[last].push(source);
+        }
+    }
+
+    routed
+        .into_iter()
+        .enumerate()
+        .filter(|(_, sources)| !sources.is_empty())
+        .map(|(i, sources)| {
+            let routed = Payload {
+                id: payload.id,
+                agent_name: payload.agent_name.clone(),
+                agent: payload.agent.clone(),
+                sources,
+            };
+            (i, routed)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SendType;
+
+    fn output(name: &str, sources: &[&str]) -> OutputConfig {
+        let mut output = OutputConfig::new(
+            name.to_string(),
+            "http://localhost:8080/log".to_string(),
+            SendType::HTTP,
+        );
+        output.sources = sources.iter().map(|s| s.to_string()).collect();
+        output
+    }
+
+    fn route(name: &str, sources: &[&str]) -> Route {
+        let (payload_sender, _) = mpsc::channel(1);
+        Route::new(&output(name, sources), payload_sender)
+    }
+
+    fn payload(source_names: &[&str]) -> Payload {
+        let sources = source_names
+            .iter()
+            .map(|name| Source::new(name.to_string(), None, Vec::new()))
+            .collect();
+
+        Payload::with_agent_name("agent".to_string(), sources)
+    }
+
+    fn source_names(payload: &Payload) -> Vec<&str> {
+        payload.sources.iter().map(|s| s.source_name.as_str()).collect()
+    }
+
+    #[test]
+    fn route_matches_name_and_pattern() {
+        let route = route("security", &["security-*", "auth"]);
+
+        assert!(route.matches("security-audit"));
+        assert!(route.matches("auth"));
+        assert!(!route.matches("app1"));
+    }
+
+    #[test]
+    fn empty_patterns_match_every_source() {
+        assert!(route("mirror", &[]).matches("anything"));
+    }
+
+    #[test]
+    fn split_payload_dispatches_sources_independently() {
+        let routes = vec![
+            route("security", &["security-*"]),
+            route("app", &["app*"]),
+            route("mirror", &[]),
+        ];
+
+        let routed = split_payload(&routes, payload(&["security-auth", "app1", "app2"]));
+
+        assert_eq!(routed.len(), 3);
+        assert_eq!(routed[0].0, 0);
+        assert_eq!(source_names(&routed[0].1), vec!["security-auth"]);
+        assert_eq!(routed[1].0, 1);
+        assert_eq!(source_names(&routed[1].1), vec!["app1", "app2"]);
+        assert_eq!(routed[2].0, 2);
+        assert_eq!(source_names(&routed[2].1), vec!["security-auth", "app1", "app2"]);
+    }
+
+    #[test]
+    fn split_payload_skips_routes_without_sources() {
+        let routes = vec![route("security", &["security-*"]), route("app", &["app*"])];
+
+        let routed = split_payload(&routes, payload(&["app1"]));
+
+        assert_eq!(routed.len(), 1);
+        assert_eq!(routed[0].0, 1);
+    }
+}
@@ -0,0 +1,66 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+// bounded in-memory buffer for payloads that could not be sent yet
+pub struct Spool<T> {
+    queue: Mutex<VecDeque<T>>,
+    capacity: usize,
+}
+
+impl<T> Spool<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            queue: Mutex::new(VecDeque::new()),
+            capacity,
+        }
+    }
+
+    // returns the oldest payload when the spool is full
+    pub fn push(&self, payload: T) -> Option<T> {
+        let mut queue = self.queue.lock().unwrap_or_else(|e| e.into_inner());
+        let dropped = if queue.len() >= self.capacity { queue.pop_front() } else { None };
+
+        if self.capacity > 0 {
+            queue.push_back(payload);
+            dropped
+        } else {
+            Some(payload)
+        }
+    }
+
+    // puts back a payload that was taken but could not be sent, it stays the oldest
+    pub fn push_front(&self, payload: T) {
+        self.queue.lock().unwrap_or_else(|e| e.into_inner()).push_front(payload);
+    }
+
+    pub fn pop(&self) -> Option<T> {
+        self.queue.lock().unwrap_or_else(|e| e.into_inner()).pop_front()
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sender::payload::Payload;
+
+    fn payload(agent_name: &str) -> Payload {
+        Payload::with_agent_name(agent_name.to_string(), Vec::new())
+    }
+
+    #[test]
+    fn full_spool_drops_oldest() {
+        let spool = Spool::new(2);
+
+        assert!(spool.push(payload("1")).is_none());
+        assert!(spool.push(payload("2")).is_none());
+        let dropped = spool.push(payload("3")).expect("oldest dropped");
+
+        assert_eq!(dropped.agent_name, "1");
+        assert_eq!(spool.len(), 2);
+        assert_eq!(spool.pop().map(|p| p.agent_name), Some("2".to_string()));
+    }
+}
@@ -1,145 +1,144 @@
 use crate::{
-    config::global_config,
+    config::{global_config, OutputConfig},
+    health::{health, OutputHealth, Reachability},
+    metrics::{metrics, OutputMetrics},
     sender::{
+        delivery::{Delivery, DeliveryOutcome},
         payload::Payload,
+        retry_scheduler::RetryScheduler,
+        RetryGuard,
+        SendResult,
         Sender,
         SenderError
     }
 };
 use async_trait::async_trait;
-use reqwest::Client;
-use std::sync::Arc;
-use std::time::Duration;
-use tokio::sync::{mpsc, Mutex};
+use reqwest::{Client, StatusCode};
+use std::sync::{Arc, OnceLock, Weak};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
 use tracing::{debug, error, trace, warn};
 
+mod backpressure;
+use self::backpressure::backpressure;
+
+mod endpoint_pool;
+use self::endpoint_pool::EndpointPool;
+
 mod http_error;
 use self::http_error::HttpError;
 
 pub struct HttpSenderStrategy {
-    client: Client, // already use Arc
-    endpoint: String,
+    transport: Arc<HttpTransport>,
     max_retry_count: u8,
     retry_delay: Duration,
-    retry_sender: mpsc::Sender<RetryPayload>,
+    retry_scheduler: RetryScheduler<RetryPayload>,
+}
+
+// everything needed for one attempt, shared by the first send and scheduled retries
+struct HttpTransport {
+    name: Arc<str>,
+    client: Client, // already use Arc
+    endpoints: EndpointPool,
+    metrics: Arc<OutputMetrics>,
+    health: Arc<OutputHealth>,
+    retry_guard: OnceLock<Weak<dyn RetryGuard>>,
 }
 
 struct RetryPayload {
-    payload: Arc<Payload>,
+    payload: Payload,
+    delivery: Delivery,
     attempt: u8,
 }
 
 impl RetryPayload {
-    pub fn new(payload: Arc<Payload>) -> Self {
+    pub fn new(payload: Payload, delivery: Delivery) -> Self {
         Self {
             payload,
+            delivery,
             attempt: 1,
         }
     }
 }
 
 impl HttpSenderStrategy {
-    pub fn build() -> Result<Self, SenderError> {
+    pub fn build(output: &OutputConfig) -> Result<Self, SenderError> {
         let client = Client::builder()
             .timeout(Duration::from_secs(10))
             .build()?;
 
         let global_config = global_config();
 
-        let (retry_sender, retry_receiver) = mpsc::channel::<RetryPayload>(global_config.channel_bound);
-
-        let strategy = Self {
-            client: client.clone(),
-            endpoint: global_config.end_point.clone(),
-            max_retry_count: global_config.retry_count,
-            retry_delay: Duration::from_millis(global_config.retry_delay_ms),
-            retry_sender,
-        };
+        let name: Arc<str> = Arc::from(output.name.as_str());
+        let transport = Arc::new(HttpTransport {
+            endpoints: EndpointPool::new(name.clone(), output),
+            metrics: metrics().output(&name),
+            health: health().output(&name),
+            retry_guard: OnceLock::new(),
+            name,
+            client,
+        });
 
-        strategy.spawn_retry_task(retry_receiver, global_config.max_send_task);
+        let max_retry_count = output.retry_count.unwrap_or(global_config.retry_count);
+        let retry_delay = Duration::from_millis(output.retry_delay_ms.unwrap_or(global_config.retry_delay_ms));
 
-        Ok(strategy)
-    }
+        let retry_scheduler = {
+            let transport = transport.clone();
+            RetryScheduler::spawn(
+                global_config.channel_bound,
+                global_config.max_send_task as usize,
+                move |retry_payload| {
+                    Self::process_retry(transport.clone(), retry_payload, max_retry_count, retry_delay)
+                },
+            )
+        };
 
-    fn spawn_retry_task(&self, retry_receiver: mpsc::Receiver<RetryPayload>, max_task_count: u8) {
-        let retry_receiver = Arc::new(Mutex::new(retry_receiver));
-
-        for _ in 0..max_task_count {
-            let client = self.client.clone();
-            let endpoint = self.endpoint.clone();
-            let max_retry_count = self.max_retry_count;
-            let retry_delay = self.retry_delay;
-
-            let retry_receiver = retry_receiver.clone();
-
-            tokio::spawn(
-                Self::retry_worker_loop(
-                    retry_receiver,
-                    client,
-                    endpoint,
-                    max_retry_count,
-                    retry_delay
-                )
-            );
-        }
-    }
-
-    async fn retry_worker_loop(
-        retry_receiver: Arc<Mutex<mpsc::Receiver<RetryPayload>>>,
-        client: Client,
-        endpoint: String,
-        max_retry_count: u8,
-        retry_delay: Duration,
-    ) {
-        loop {
-            // TODO receiver.recv() is not parallelism should be remove mutex.. but how?
-            let retry_payload = {
-                let mut retry_receiver = retry_receiver.lock().await;
-                match retry_receiver.recv().await {
-                    Some(retry_payload) => retry_payload,
-                    None => {
-                        error!("Failed to retry channel close");
-                        break;
-                    },
-                }
-            };
-
-            Self::process_retry(
-                &client,
-                &endpoint,
-                retry_payload,
-                max_retry_count,
-                retry_delay
-            ).await;
-        }
+        Ok(Self {
+            transport,
+            max_retry_count,
+            retry_delay,
+            retry_scheduler,
+        })
     }
 
+    // one retry attempt, returns the payload with its next backoff when it should be retried again
     async fn process_retry(
-        client: &Client,
-        endpoint: &str,
-        mut retry_payload: RetryPayload,
+        transport: Arc<HttpTransport>,
+        retry_payload: RetryPayload,
         max_retry_count: u8,
         retry_delay: Duration,
-    ) {
-        while retry_payload.attempt < max_retry_count {
-            let backoff = Self::calc_backoff(retry_delay, retry_payload.attempt);
-            tokio::time::sleep(backoff).await;
-            retry_payload.attempt += 1;
-
-            match Self::try_send(&client, &endpoint, retry_payload.payload.as_ref()).await {
-                Ok(()) => {
-                    debug!("HTTP retry success. attempt {}/{max_retry_count}", retry_payload.attempt);
-                    return;
-                }
-                Err(HttpError::NonRetryable(e)) => {
-                    error!("HTTP retry failed (non-retryable) attempt {}/{max_retry_count}: {e}", retry_payload.attempt);
-                    return;
-                }
-                Err(HttpError::Retryable(e)) => warn!("HTTP retry failed (retryable) attempt {}/{max_retry_count}: {e}", retry_payload.attempt),
+    ) -> Option<(RetryPayload, Duration)> {
+        let name = &transport.name;
+        let RetryPayload { payload, delivery, attempt } = retry_payload;
+        let (payload, delivery) = transport.release_retry(payload, delivery)?;
+        let retry_payload = RetryPayload { payload, delivery, attempt: attempt + 1 };
+        transport.metrics.retried(retry_payload.attempt);
+
+        match transport.try_send(&retry_payload.payload).await {
+            Ok(()) => {
+                debug!("[{name}] HTTP retry success. attempt {}/{max_retry_count}", retry_payload.attempt);
+                retry_payload.delivery.resolve(DeliveryOutcome::Delivered);
+                None
             }
-        }
+            Err(HttpError::NonRetryable(e)) => {
+                error!("[{name}] HTTP retry failed (non-retryable) attempt {}/{max_retry_count}: {e}", retry_payload.attempt);
+                retry_payload.delivery.resolve(DeliveryOutcome::FailedPermanently);
+                transport.metrics.failed();
+                None
+            }
+            Err(HttpError::Retryable(e)) if retry_payload.attempt >= max_retry_count => {
+                error!("[{name}] HTTP retry failed after {} attempts (max: {max_retry_count}): {e}", retry_payload.attempt);
+                retry_payload.delivery.resolve(DeliveryOutcome::FailedPermanently);
+                transport.metrics.failed();
+                None
+            }
+            Err(HttpError::Retryable(e)) => {
+                warn!("[{name}] HTTP retry failed (retryable) attempt {}/{max_retry_count}: {e}", retry_payload.attempt);
 
-        error!("HTTP retry failed after {} attempts (max: {max_retry_count})", retry_payload.attempt);
+                let backoff = Self::calc_backoff(retry_delay, retry_payload.attempt);
+                Some((retry_payload, backoff))
+            }
+        }
     }
 
     fn calc_backoff(base_delay: Duration, attempt: u8) -> Duration {
@@ -147,17 +146,64 @@ impl HttpSenderStrategy {
 
         // base_delay * 2^(attempt-1)
         let backoff = base_delay * 2_u32.pow((attempt - 1) as u32);
-        if MAX_DELAY > backoff { backoff } else { MAX_DELAY }
+        let backoff = if MAX_DELAY > backoff { backoff } else { MAX_DELAY };
+
+        // equal jitter: keep half and randomize the rest so a fleet of agents does not retry in lockstep
+        let half = backoff / 2;
+        half + half.mul_f64(fastrand::f64())
+    }
+}
+
+impl HttpTransport {
+    // none when the guard took the payload over
+    fn release_retry(&self, payload: Payload, delivery: Delivery) -> Option<(Payload, Delivery)> {
+        match self.retry_guard.get().and_then(Weak::upgrade) {
+            Some(guard) => guard.hold(payload, delivery),
+            None => Some((payload, delivery)),
+        }
+    }
+
+    // only retryable failures count against endpoint health, 4xx is a problem of the payload.
+    // first attempts and retries both update the reachability of the output
+    async fn try_send(&self, payload: &Payload) -> Result<(), HttpError> {
+        backpressure().wait().await;
+
+        let index = self.endpoints.pick();
+        let started = Instant::now();
+        let result = self.post(self.endpoints.url(index), payload).await;
+        self.metrics.send_duration.observe(started.elapsed());
+
+        match result {
+            Ok(()) => self.endpoints.record_success(index),
+            Err(HttpError::Retryable(_)) => self.endpoints.record_failure(index),
+            Err(HttpError::NonRetryable(_)) => {}
+        }
+
+        // a rejected payload still reached the server
+        self.health.set_reachability(match result {
+            Err(HttpError::Retryable(_)) => Reachability::Unreachable,
+            _ => Reachability::Reachable,
+        });
+
+        result
     }
 
     // reqwest is 4xx, 5xx error not return reqwest::Error
     // use error_for_status() then mapping reqwest::Error
-    async fn try_send(client: &Client, endpoint: &str, payload: &Payload) -> Result<(), HttpError> {
-        client.post(endpoint)
+    async fn post(&self, endpoint: &str, payload: &Payload) -> Result<(), HttpError> {
+        let response = self.client.post(endpoint)
             .json(payload)
             .send()
-            .await?
-            .error_for_status()?;
+            .await?;
+
+        // 429 and 503 may tell us how long to back off, pause the senders of every output
+        if matches!(response.status(), StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE) {
+            if let Some(retry_after) = backpressure::parse_retry_after(response.headers()) {
+                backpressure().pause(&self.name, retry_after);
+            }
+        }
+
+        response.error_for_status()?;
 
         Ok(())
     }
@@ -165,20 +211,70 @@ impl HttpSenderStrategy {
 
 #[async_trait]
 impl Sender for HttpSenderStrategy {
-    async fn send(&self, payload: Payload) {
-        let payload = Arc::new(payload);
-        let endpoint = self.endpoint.as_str();
+    async fn send(&self, payload: Payload, delivery: Delivery) -> SendResult {
+        let name = &self.transport.name;
 
-        match Self::try_send(&self.client, endpoint, payload.as_ref()).await {
-            Ok(()) => trace!("HTTP send success."),
-            Err(HttpError::NonRetryable(e)) => error!("HTTP send failed (non-retryable): {e}"),
+        match self.transport.try_send(&payload).await {
+            Ok(()) => {
+                trace!("[{name}] HTTP send success.");
+                delivery.resolve(DeliveryOutcome::Delivered);
+                SendResult::Delivered
+            }
+            Err(HttpError::NonRetryable(e)) => {
+                error!("[{name}] HTTP send failed (non-retryable): {e}");
+                delivery.resolve(DeliveryOutcome::FailedPermanently);
+                self.transport.metrics.failed();
+                SendResult::Rejected
+            }
+            Err(HttpError::Retryable(e)) if self.max_retry_count <= 1 => {
+                error!("[{name}] HTTP send failed after 1 attempts (max: {}): {e}", self.max_retry_count);
+                delivery.resolve(DeliveryOutcome::FailedPermanently);
+                self.transport.metrics.failed();
+                SendResult::Unreachable
+            }
             Err(HttpError::Retryable(e)) => {
-                warn!("HTTP send failed (retryable) attempt 1/{}: {e}", self.max_retry_count);
+                warn!("[{name}] HTTP send failed (retryable) attempt 1/{}: {e}", self.max_retry_count);
 
-                if let Err(e) = self.retry_sender.send(RetryPayload::new(payload)).await {
-                    error!("Failed to retry channel close: {e}");
+                let Some((payload, delivery)) = self.transport.release_retry(payload, delivery) else {
+                    return SendResult::Unreachable;
+                };
+                let backoff = Self::calc_backoff(self.retry_delay, 1);
+                let retry_payload = RetryPayload::new(payload, delivery);
+                if let Err(mpsc::error::SendError(retry_payload)) = self.retry_scheduler.schedule(retry_payload, backoff).await {
+                    error!("[{name}] Failed to retry channel close");
+                    retry_payload.delivery.resolve(DeliveryOutcome::FailedPermanently);
+                    self.transport.metrics.failed();
                 }
+                SendResult::Unreachable
             }
         }
     }
+
+    fn guard_retries(&self, guard: Weak<dyn RetryGuard>) {
+        let _ = self.transport.retry_guard.set(guard);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_grows_exponentially_with_jitter() {
+        let base_delay = Duration::from_millis(100);
+
+        for attempt in 1..=5 {
+            let backoff = base_delay * 2_u32.pow((attempt - 1) as u32);
+            let jittered = HttpSenderStrategy::calc_backoff(base_delay, attempt);
+
+            assert!(jittered >= backoff / 2 && jittered <= backoff, "attempt {attempt}: {jittered:?}");
+        }
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        let jittered = HttpSenderStrategy::calc_backoff(Duration::from_secs(10), 10);
+
+        assert!(jittered >= Duration::from_secs(15) && jittered <= Duration::from_secs(30));
+    }
 }
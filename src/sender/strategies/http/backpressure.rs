@@ -0,0 +1,105 @@
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+use tracing::warn;
+
+// never trust a server to pause us for longer than this
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(300);
+
+static BACKPRESSURE: Backpressure = Backpressure::new();
+
+// server-driven pause shared by every send and retry of every output, a Retry-After from any
+// endpoint holds back all outputs and their failover endpoints until it ends
+pub fn backpressure() -> &'static Backpressure {
+    &BACKPRESSURE
+}
+
+pub struct Backpressure {
+    paused_until: Mutex<Option<Instant>>,
+}
+
+impl Backpressure {
+    const fn new() -> Self {
+        Self {
+            paused_until: Mutex::new(None),
+        }
+    }
+
+    // name is the output whose server asked for the pause
+    pub fn pause(&self, name: &str, retry_after: Duration) {
+        let until = Instant::now() + retry_after.min(MAX_RETRY_AFTER);
+        let mut paused_until = self.paused_until.lock().unwrap_or_else(|e| e.into_inner());
+
+        if paused_until.is_none_or(|current| current < until) {
+            *paused_until = Some(until);
+            warn!("[{name}] server requested backpressure, pausing sends of all outputs for {}ms", retry_after.as_millis());
+        }
+    }
+
+    pub async fn wait(&self) {
+        let until = *self.paused_until.lock().unwrap_or_else(|e| e.into_inner());
+
+        if let Some(until) = until {
+            tokio::time::sleep_until(until.into()).await;
+        }
+    }
+}
+
+// Retry-After is either delay-seconds or an HTTP-date
+pub fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(date.duration_since(SystemTime::now()).unwrap_or(Duration::ZERO))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn headers(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn parse_delay_seconds() {
+        assert_eq!(parse_retry_after(&headers("120")), Some(Duration::from_secs(120)));
+    }
+
+    #[test]
+    fn parse_http_date() {
+        let date = httpdate::fmt_http_date(SystemTime::now() + Duration::from_secs(60));
+        let retry_after = parse_retry_after(&headers(&date)).expect("parse http date");
+
+        assert!(retry_after > Duration::from_secs(55) && retry_after <= Duration::from_secs(60));
+    }
+
+    #[test]
+    fn past_http_date_is_zero() {
+        assert_eq!(parse_retry_after(&headers("Wed, 21 Oct 2015 07:28:00 GMT")), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn invalid_or_missing_is_none() {
+        assert_eq!(parse_retry_after(&headers("soon")), None);
+        assert_eq!(parse_retry_after(&HeaderMap::new()), None);
+    }
+
+    #[tokio::test]
+    async fn wait_until_pause_elapsed() {
+        let backpressure = Backpressure::new();
+        backpressure.pause("test", Duration::from_millis(50));
+
+        let started = Instant::now();
+        backpressure.wait().await;
+
+        assert!(started.elapsed() >= Duration::from_millis(45));
+    }
+}
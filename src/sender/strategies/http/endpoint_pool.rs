@@ -0,0 +1,188 @@
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::config::{BalanceMode, OutputConfig};
+
+struct Endpoint {
+    url: String,
+    consecutive_failures: AtomicU32,
+    unhealthy_until: Mutex<Option<Instant>>,
+}
+
+impl Endpoint {
+    fn new(url: String) -> Self {
+        Self {
+            url,
+            consecutive_failures: AtomicU32::new(0),
+            unhealthy_until: Mutex::new(None),
+        }
+    }
+
+    fn unhealthy_until(&self) -> Option<Instant> {
+        *self.unhealthy_until.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn is_healthy(&self, now: Instant) -> bool {
+        self.unhealthy_until().is_none_or(|until| now >= until)
+    }
+}
+
+// shared between the first send and retry workers so all of them agree on endpoint health
+pub struct EndpointPool {
+    name: Arc<str>,
+    endpoints: Vec<Endpoint>,
+    balance: BalanceMode,
+    unhealthy_threshold: u32,
+    cooldown: Duration,
+    next: AtomicUsize,
+}
+
+impl EndpointPool {
+    pub fn new(name: Arc<str>, output: &OutputConfig) -> Self {
+        Self {
+            name,
+            endpoints: output.end_points().cloned().map(Endpoint::new).collect(),
+            balance: output.balance,
+            unhealthy_threshold: output.unhealthy_threshold,
+            cooldown: Duration::from_secs(output.unhealthy_cooldown_secs),
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn url(&self, index: usize) -> &str {
+        &self.endpoints[index].url
+    }
+
+    // pick a healthy endpoint, when every endpoint is unhealthy use the one recovering first
+    pub fn pick(&self) -> usize {
+        let now = Instant::now();
+        let len = self.endpoints.len();
+        let start = match self.balance {
+            BalanceMode::Failover => 0,
+            BalanceMode::RoundRobin => self.next.fetch_add(1, Ordering::Relaxed) % len,
+        };
+
+        (0..len)
+            .map(|offset| (start + offset) % len)
+            .find(|&i| self.endpoints[i].is_healthy(now))
+            .unwrap_or_else(|| {
+                (0..len)
+                    .min_by_key(|&i| self.endpoints[i].unhealthy_until())
+                    .unwrap_or(0)
+            })
+    }
+
+    pub fn record_success(&self, index: usize) {
+        let endpoint = &self.endpoints[index];
+        endpoint.consecutive_failures.store(0, Ordering::Relaxed);
+
+        let mut unhealthy_until = endpoint.unhealthy_until.lock().unwrap_or_else(|e| e.into_inner());
+        if unhealthy_until.take().is_some() {
+            info!("[{}] endpoint {} is healthy again", self.name, endpoint.url);
+        }
+    }
+
+    pub fn record_failure(&self, index: usize) {
+        let endpoint = &self.endpoints[index];
+        let failures = endpoint.consecutive_failures.fetch_add(1, Ordering::Relaxed) + 1;
+        if failures < self.unhealthy_threshold {
+            return;
+        }
+
+        endpoint.consecutive_failures.store(0, Ordering::Relaxed);
+        *endpoint.unhealthy_until.lock().unwrap_or_else(|e| e.into_inner()) = Some(Instant::now() + self.cooldown);
+
+        warn!(
+            "[{}] endpoint {} marked unhealthy for {}s after {failures} consecutive failures",
+            self.name,
+            endpoint.url,
+            self.cooldown.as_secs()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SendType;
+
+    fn pool(balance: BalanceMode, cooldown_secs: u64) -> EndpointPool {
+        let mut output = OutputConfig::new(
+            "test".to_string(),
+            "http://primary/log".to_string(),
+            SendType::HTTP,
+        );
+        output.failover_end_points = vec!["http://secondary/log".to_string(), "http://tertiary/log".to_string()];
+        output.balance = balance;
+        output.unhealthy_threshold = 2;
+        output.unhealthy_cooldown_secs = cooldown_secs;
+
+        EndpointPool::new(Arc::from("test"), &output)
+    }
+
+    #[test]
+    fn failover_prefers_primary() {
+        let pool = pool(BalanceMode::Failover, 30);
+
+        assert_eq!(pool.pick(), 0);
+        assert_eq!(pool.pick(), 0);
+    }
+
+    #[test]
+    fn consecutive_failures_move_to_next_endpoint() {
+        let pool = pool(BalanceMode::Failover, 30);
+
+        pool.record_failure(0);
+        assert_eq!(pool.pick(), 0);
+
+        pool.record_failure(0);
+        assert_eq!(pool.pick(), 1);
+        assert_eq!(pool.url(pool.pick()), "http://secondary/log");
+    }
+
+    #[test]
+    fn success_resets_failure_count() {
+        let pool = pool(BalanceMode::Failover, 30);
+
+        pool.record_failure(0);
+        pool.record_success(0);
+        pool.record_failure(0);
+
+        assert_eq!(pool.pick(), 0);
+    }
+
+    #[test]
+    fn endpoint_recovers_after_cooldown() {
+        let pool = pool(BalanceMode::Failover, 0);
+
+        pool.record_failure(0);
+        pool.record_failure(0);
+
+        assert_eq!(pool.pick(), 0);
+    }
+
+    #[test]
+    fn all_unhealthy_picks_first_recovering() {
+        let pool = pool(BalanceMode::Failover, 30);
+
+        for index in [1, 0, 2] {
+            pool.record_failure(index);
+            pool.record_failure(index);
+        }
+
+        assert_eq!(pool.pick(), 1);
+    }
+
+    #[test]
+    fn round_robin_skips_unhealthy() {
+        let pool = pool(BalanceMode::RoundRobin, 30);
+
+        pool.record_failure(1);
+        pool.record_failure(1);
+
+        let picked: Vec<usize> = (0..4).map(|_| pool.pick()).collect();
+        assert_eq!(picked, vec![0, 2, 2, 0]);
+    }
+}
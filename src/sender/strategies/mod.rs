@@ -1,12 +1,12 @@
 use self::http::HttpSenderStrategy;
 use super::{Sender, SenderError};
-use crate::config::{global_config, SendType};
+use crate::config::{OutputConfig, SendType};
 use std::sync::Arc;
 
 mod http;
 
-pub fn build_sender() -> Result<Arc<dyn Sender>, SenderError> {
-    match global_config().send_type {
-        SendType::HTTP => Ok(Arc::new(HttpSenderStrategy::build()?)),
+pub fn build_sender(output: &OutputConfig) -> Result<Arc<dyn Sender>, SenderError> {
+    match output.send_type {
+        SendType::HTTP => Ok(Arc::new(HttpSenderStrategy::build(output)?)),
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,173 @@
+use std::{io, time::Duration};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    task, time,
+};
+use tracing::{debug, info};
+
+use crate::{
+    health::{health, Report},
+    metrics::metrics,
+};
+
+const MAX_REQUEST_HEAD: usize = 8 * 1024;
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: String,
+}
+
+// a minimal HTTP/1.1 responder for metrics and health checks,
+// every connection serves one request and is closed
+pub fn spawn_status_server(listener: TcpListener) -> task::JoinHandle<()> {
+    if let Ok(addr) = listener.local_addr() {
+        info!("serving /metrics, /healthz and /readyz on http://{addr}");
+    }
+
+    tokio::spawn(async move {
+        loop {
+            let (stream, peer) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    debug!("failed to accept status connection: {e}");
+                    continue;
+                }
+            };
+
+            tokio::spawn(async move {
+                if let Err(e) = handle(stream).await {
+                    debug!("status request from {peer} failed: {e}");
+                }
+            });
+        }
+    })
+}
+
+async fn handle(mut stream: TcpStream) -> io::Result<()> {
+    let head = time::timeout(READ_TIMEOUT, read_head(&mut stream))
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "request head timed out"))??;
+
+    let mut request_line = head.lines().next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().unwrap_or_default();
+    let path = request_line.next().unwrap_or_default();
+
+    let response = route(method, path);
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        response.content_type,
+        response.body.len()
+    );
+
+    stream.write_all(head.as_bytes()).await?;
+    if method != "HEAD" {
+        stream.write_all(response.body.as_bytes()).await?;
+    }
+    stream.shutdown().await
+}
+
+async fn read_head(stream: &mut TcpStream) -> io::Result<String> {
+    let mut head = Vec::with_capacity(1024);
+    let mut buf = [0; 1024];
+
+    while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+        if head.len() > MAX_REQUEST_HEAD {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "request head too large"));
+        }
+
+        let read = stream.read(&mut buf).await?;
+        if read == 0 {
+            break;
+        }
+        head.extend_from_slice(&buf[..read]);
+    }
+
+    Ok(String::from_utf8_lossy(&head).into_owned())
+}
+
+fn route(method: &str, path: &str) -> Response {
+    // query strings like ?name[]=x from scrapers are ignored
+    let path = path.split('?').next().unwrap_or_default();
+
+    match (method, path) {
+        ("GET" | "HEAD", "/metrics") => Response {
+            status: "200 OK",
+            content_type: "text/plain; version=0.0.4; charset=utf-8",
+            body: metrics().render(),
+        },
+        ("GET" | "HEAD", "/healthz") => health_response(health().liveness()),
+        ("GET" | "HEAD", "/readyz") => health_response(health().readiness()),
+        ("GET" | "HEAD", _) => Response {
+            status: "404 Not Found",
+            content_type: "text/plain; charset=utf-8",
+            body: "not found\n".to_string(),
+        },
+        _ => Response {
+            status: "405 Method Not Allowed",
+            content_type: "text/plain; charset=utf-8",
+            body: "method not allowed\n".to_string(),
+        },
+    }
+}
+
+fn health_response(report: Report) -> Response {
+    Response {
+        status: if report.ok { "200 OK" } else { "503 Service Unavailable" },
+        content_type: "application/json",
+        body: report.body.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn request(addr: std::net::SocketAddr, request: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(request.as_bytes()).await.unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn serves_metrics() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        spawn_status_server(listener);
+
+        metrics().source("server-test").lines_read.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+
+        let response = request(addr, "GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("log_agent_lines_read_total{source=\"server-test\"} 1\n"));
+
+        let response = request(addr, "GET /other HTTP/1.1\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+
+        let response = request(addr, "POST /metrics HTTP/1.1\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+    }
+
+    #[tokio::test]
+    async fn serves_health_checks() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        spawn_status_server(listener);
+
+        let _guard = health().source("server-test").running();
+
+        let response = request(addr, "GET /healthz HTTP/1.1\r\n\r\n").await;
+        assert!(response.contains("Content-Type: application/json\r\n"));
+        assert!(response.contains(r#""server-test":{"restarts":0,"state":"running"}"#));
+
+        // readiness depends on the shared registry, only the shape is checked here
+        let response = request(addr, "GET /readyz HTTP/1.1\r\n\r\n").await;
+        assert!(response.contains(r#""config_loaded":"#));
+    }
+}
@@ -0,0 +1,301 @@
+use serde_json::{json, Value};
+use std::{
+    sync::{atomic::Ordering, Arc},
+    time::{Duration, Instant},
+};
+use tracing::{info, trace};
+
+use crate::{
+    config::{
+        source_config::{SampleMode, SamplingConfig},
+        SourceConfig,
+    },
+    log_event::{LogEvent, SourceMeta},
+    metrics::{metrics, SourceMetrics},
+    parser::Fields,
+    processor::{Outcome, Processor},
+};
+
+mod token_bucket;
+use self::token_bucket::TokenBucket;
+
+const SUMMARY_INTERVAL: Duration = Duration::from_secs(10);
+
+// lines dropped since the last summary
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct DropSummary {
+    pub rate_limited: u64,
+    pub sampled_out: u64,
+    pub interval: Duration,
+}
+
+impl DropSummary {
+    pub fn total(&self) -> u64 {
+        self.rate_limited + self.sampled_out
+    }
+}
+
+// rate limits and samples the lines of one source so a noisy source cannot flood the channel
+pub struct Throttle {
+    source_name: String,
+    lines: Option<TokenBucket>,
+    bytes: Option<TokenBucket>,
+    sampling: Option<SamplingConfig>,
+    dropped: DropSummary,
+    metrics: Arc<SourceMetrics>,
+    last_summary: Instant,
+    // where the summary event is reported, taken from the latest line
+    last_meta: Option<Arc<SourceMeta>>,
+    last_offset: u64,
+}
+
+impl Throttle {
+    // the `rate_limit` processor, best placed before `parse` so dropped lines cost as little as possible
+    pub fn rate_limit(source: &SourceConfig) -> Option<Self> {
+        let rate_limit = source.rate_limit.as_ref()?;
+        let now = Instant::now();
+
+        let mut throttle = Self::new(source, now);
+        throttle.lines = rate_limit.lines_per_sec.map(|rate| TokenBucket::new(rate, now));
+        throttle.bytes = rate_limit.bytes_per_sec.map(|rate| TokenBucket::new(rate, now));
+        Some(throttle)
+    }
+
+    // the `sample` processor, placed after `parse` when hash mode uses a parsed field as key
+    pub fn sampling(source: &SourceConfig) -> Option<Self> {
+        let mut throttle = Self::new(source, Instant::now());
+        throttle.sampling = Some(source.sampling.clone()?);
+        Some(throttle)
+    }
+
+    fn new(source: &SourceConfig, now: Instant) -> Self {
+        Self {
+            source_name: source.name.clone(),
+            lines: None,
+            bytes: None,
+            sampling: None,
+            dropped: DropSummary::default(),
+            metrics: metrics().source(&source.name),
+            last_summary: now,
+            last_meta: None,
+            last_offset: 0,
+        }
+    }
+
+    fn accept_line(&mut self, line: &str) -> bool {
+        self.accept_line_at(line, Instant::now())
+    }
+
+    fn accept_line_at(&mut self, line: &str, now: Instant) -> bool {
+        if let Some(lines) = &mut self.lines {
+            if !lines.try_take(1, now) {
+                return self.rate_limited();
+            }
+        }
+
+        if let Some(bytes) = &mut self.bytes {
+            if !bytes.try_take(line.len() as u64, now) {
+                if let Some(lines) = &mut self.lines {
+                    lines.refund(1);
+                }
+                return self.rate_limited();
+            }
+        }
+
+        true
+    }
+
+    fn sample(&mut self, line: &str, fields: &Fields) -> bool {
+        let Some(sampling) = &self.sampling else { return true };
+
+        let keep = match sampling.mode {
+            SampleMode::Random => fastrand::f64() < sampling.rate,
+            SampleMode::Hash => {
+                let key = match sampling.key.as_ref().and_then(|key| fields.get(key)) {
+                    Some(Value::String(value)) => value.clone(),
+                    Some(value) => value.to_string(),
+                    None => line.to_string(),
+                };
+                keep_by_hash(&key, sampling.rate)
+            }
+        };
+
+        if !keep {
+            trace!("[{}] sampled out line", self.source_name);
+            self.dropped.sampled_out += 1;
+            self.metrics.sampled_out.fetch_add(1, Ordering::Relaxed);
+        }
+
+        keep
+    }
+
+    // returns what was dropped once per interval, nothing when no line was dropped
+    fn take_summary(&mut self) -> Option<DropSummary> {
+        let elapsed = self.last_summary.elapsed();
+        if elapsed < SUMMARY_INTERVAL {
+            return None;
+        }
+
+        self.last_summary = Instant::now();
+        let summary = DropSummary { interval: elapsed, ..std::mem::take(&mut self.dropped) };
+
+        (summary.total() > 0).then_some(summary)
+    }
+
+    fn rate_limited(&mut self) -> bool {
+        trace!("[{}] rate limited line", self.source_name);
+        self.dropped.rate_limited += 1;
+        self.metrics.rate_limited.fetch_add(1, Ordering::Relaxed);
+        false
+    }
+}
+
+impl Processor for Throttle {
+    fn process(&mut self, event: LogEvent) -> Outcome {
+        self.last_meta = event.meta.clone();
+        self.last_offset = event.offset;
+
+        if self.accept_line(&event.log) && self.sample(&event.log, &event.fields) {
+            Outcome::Keep(event)
+        } else {
+            Outcome::Drop
+        }
+    }
+
+    // tells the backend that lines are missing on purpose
+    fn flush(&mut self) -> Vec<LogEvent> {
+        let Some(summary) = self.take_summary() else { return Vec::new() };
+
+        let name = &self.source_name;
+        let secs = summary.interval.as_secs();
+        info!(
+            "[{name}] dropped {} lines in last {secs}s (rate limited: {}, sampled out: {})",
+            summary.total(), summary.rate_limited, summary.sampled_out
+        );
+
+        let log = format!("dropped {} lines from {name} in last {secs}s", summary.total());
+        let mut event = LogEvent::new(name.clone(), log, self.last_offset);
+        event.meta = self.last_meta.clone();
+        if let Value::Object(fields) = json!({
+            "agent_event": "dropped_lines",
+            "dropped": summary.total(),
+            "rate_limited": summary.rate_limited,
+            "sampled_out": summary.sampled_out,
+            "interval_secs": secs,
+        }) {
+            event.fields = fields;
+        }
+
+        vec![event]
+    }
+}
+
+// FNV-1a is stable across processes and versions, unlike the std hasher
+fn keep_by_hash(key: &str, rate: f64) -> bool {
+    let mut hash = key.bytes().fold(0xcbf29ce484222325_u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+
+    // murmur3 finalizer, keys differing only in the last bytes still spread over the whole range
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51afd7ed558ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ceb9fe1a85ec53);
+    hash ^= hash >> 33;
+
+    (hash as f64 / u64::MAX as f64) < rate
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn throttle(config: &str) -> Throttle {
+        let source: SourceConfig = toml::from_str(&format!(
+            r#"
+                name = "app1"
+                log_path = "app1.log"
+                {config}
+            "#
+        ))
+        .unwrap();
+
+        Throttle::rate_limit(&source).or_else(|| Throttle::sampling(&source)).unwrap()
+    }
+
+    #[test]
+    fn lines_per_sec() {
+        let mut throttle = throttle("rate_limit = { lines_per_sec = 2 }");
+        let now = Instant::now();
+
+        assert!(throttle.accept_line_at("a", now));
+        assert!(throttle.accept_line_at("b", now));
+        assert!(!throttle.accept_line_at("c", now));
+        assert!(throttle.accept_line_at("d", now + Duration::from_secs(1)));
+
+        assert_eq!(throttle.dropped.rate_limited, 1);
+    }
+
+    #[test]
+    fn bytes_per_sec_refunds_line_token() {
+        let mut throttle = throttle("rate_limit = { lines_per_sec = 2, bytes_per_sec = 10 }");
+        let now = Instant::now();
+
+        assert!(throttle.accept_line_at("12345678", now));
+        assert!(!throttle.accept_line_at("12345678", now));
+        // the line token of the refused line was given back
+        assert!(throttle.accept_line_at("12", now));
+    }
+
+    #[test]
+    fn hash_sampling_is_deterministic() {
+        let mut throttle = throttle(r#"sampling = { rate = 0.5, mode = "hash", key = "trace_id" }"#);
+
+        let kept: Vec<bool> = (0..200)
+            .map(|i| {
+                let fields = json!({"trace_id": format!("trace-{i}")}).as_object().unwrap().clone();
+                throttle.sample("line", &fields)
+            })
+            .collect();
+        let again: Vec<bool> = (0..200)
+            .map(|i| {
+                let fields = json!({"trace_id": format!("trace-{i}")}).as_object().unwrap().clone();
+                throttle.sample("other line", &fields)
+            })
+            .collect();
+
+        assert_eq!(kept, again);
+        let kept_count = kept.iter().filter(|&&kept| kept).count();
+        assert!((60..140).contains(&kept_count), "kept {kept_count}");
+    }
+
+    #[test]
+    fn random_sampling_keeps_about_rate() {
+        let mut throttle = throttle("sampling = { rate = 0.1 }");
+
+        let kept = (0..2000).filter(|_| throttle.sample("line", &Fields::new())).count();
+
+        assert!((100..300).contains(&kept), "kept {kept}");
+        assert_eq!(throttle.dropped.sampled_out, 2000 - kept as u64);
+    }
+
+    #[test]
+    fn summary_event_once_per_interval() {
+        let mut throttle = throttle("rate_limit = { lines_per_sec = 1 }");
+        let now = Instant::now();
+        throttle.accept_line_at("a", now);
+        throttle.accept_line_at("b", now);
+
+        assert!(throttle.flush().is_empty());
+
+        throttle.last_summary -= SUMMARY_INTERVAL;
+        let summary = throttle.flush().remove(0);
+        assert_eq!(summary.log, "dropped 1 lines from app1 in last 10s");
+        assert_eq!(summary.fields["rate_limited"], 1);
+        assert_eq!(summary.fields["agent_event"], "dropped_lines");
+
+        throttle.last_summary -= SUMMARY_INTERVAL;
+        assert!(throttle.flush().is_empty());
+    }
+}
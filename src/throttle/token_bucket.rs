@@ -0,0 +1,79 @@
+use std::time::Instant;
+
+// holds up to one second worth of tokens
+pub struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(rate_per_sec: u64, now: Instant) -> Self {
+        let rate = rate_per_sec as f64;
+
+        Self {
+            rate,
+            tokens: rate,
+            last_refill: now,
+        }
+    }
+
+    // a cost above the capacity is capped so a single long line can still pass a full bucket
+    pub fn try_take(&mut self, cost: u64, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate);
+        self.last_refill = now;
+
+        let cost = (cost as f64).min(self.rate);
+        if self.tokens < cost {
+            return false;
+        }
+
+        self.tokens -= cost;
+        true
+    }
+
+    // gives back tokens taken for a line another bucket refused
+    pub fn refund(&mut self, cost: u64) {
+        self.tokens = (self.tokens + (cost as f64).min(self.rate)).min(self.rate);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn refills_over_time() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(2, start);
+
+        assert!(bucket.try_take(1, start));
+        assert!(bucket.try_take(1, start));
+        assert!(!bucket.try_take(1, start));
+
+        assert!(!bucket.try_take(1, start + Duration::from_millis(400)));
+        assert!(bucket.try_take(1, start + Duration::from_millis(500)));
+    }
+
+    #[test]
+    fn does_not_exceed_capacity() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(3, start);
+
+        let later = start + Duration::from_secs(60);
+        assert!(bucket.try_take(3, later));
+        assert!(!bucket.try_take(1, later));
+    }
+
+    #[test]
+    fn oversized_cost_needs_full_bucket() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(100, start);
+
+        assert!(bucket.try_take(1000, start));
+        assert!(!bucket.try_take(1000, start + Duration::from_millis(500)));
+        assert!(bucket.try_take(1000, start + Duration::from_secs(1)));
+    }
+}